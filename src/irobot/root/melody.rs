@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+// A single step of a melody, lengths are in beats so the whole tune can be sped up or slowed down
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MelodyStep {
+    // Frequency in Hz
+    Note { frequency: u32, beats: f32 },
+    Rest { beats: f32 },
+}
+
+// A tune to play through RootRobot::play_melody, built up one note or rest at a time:
+// Melody::new(120).note(440, 1.0).rest(0.5).note(523, 2.0)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Melody {
+    // Beats per minute
+    pub tempo: u16,
+    pub steps: Vec<MelodyStep>,
+}
+
+impl Melody {
+    pub fn new(tempo: u16) -> Melody {
+        Melody {
+            tempo,
+            steps: vec![],
+        }
+    }
+
+    pub fn note(mut self, frequency: u32, beats: f32) -> Melody {
+        self.steps.push(MelodyStep::Note { frequency, beats });
+        self
+    }
+
+    pub fn rest(mut self, beats: f32) -> Melody {
+        self.steps.push(MelodyStep::Rest { beats });
+        self
+    }
+
+    // How long the given number of beats lasts at this tempo
+    pub fn beats_duration(&self, beats: f32) -> Duration {
+        Duration::from_secs_f32(beats.max(0.0) * 60.0 / self.tempo as f32)
+    }
+
+    // How long the whole melody takes to play
+    pub fn duration(&self) -> Duration {
+        let beats: f32 = self
+            .steps
+            .iter()
+            .map(|step| match step {
+                MelodyStep::Note { beats, .. } | MelodyStep::Rest { beats } => beats.max(0.0),
+            })
+            .sum();
+        self.beats_duration(beats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_beats_from_tempo() {
+        let melody = Melody::new(120).note(440, 1.0).rest(0.5).note(523, 2.5);
+
+        assert_eq!(melody.steps.len(), 3);
+        assert_eq!(melody.beats_duration(1.0), Duration::from_millis(500));
+        assert_eq!(melody.duration(), Duration::from_secs(2));
+    }
+}
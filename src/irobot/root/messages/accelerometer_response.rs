@@ -0,0 +1,90 @@
+use super::response_reader::ResponseReader;
+use crate::irobot::root::{Message, RootError};
+
+// Readings with this much of gravity on one axis are taken as the robot lying that way, in milli-g
+const GRAVITY_ALIGNED_MILLI_G: f32 = 800.0;
+
+// Which way up the robot is sitting
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    // Flat on a table or floor
+    Horizontal,
+    // Stuck to a wall, like a whiteboard
+    Vertical,
+    // Somewhere in between, or being moved around
+    Tilted,
+}
+
+// Acceleration along each axis in milli-g. Z points up out of the top of the robot, x and y lie in
+// the plane of its wheels.
+pub struct AccelerometerResponse {
+    pub timestamp: u32,
+    pub x: i16,
+    pub y: i16,
+    pub z: i16,
+}
+
+impl AccelerometerResponse {
+    pub fn new(message: Message) -> Result<AccelerometerResponse, RootError> {
+        let mut reader = ResponseReader::new(&message);
+        Ok(AccelerometerResponse {
+            timestamp: reader.read_u32()?,
+            x: reader.read_i16()?,
+            y: reader.read_i16()?,
+            z: reader.read_i16()?,
+        })
+    }
+
+    // Work out which way up the robot is from where gravity is pulling
+    pub fn orientation(&self) -> Orientation {
+        let across = (self.x as f32).hypot(self.y as f32);
+        if (self.z as f32).abs() >= GRAVITY_ALIGNED_MILLI_G {
+            Orientation::Horizontal
+        } else if across >= GRAVITY_ALIGNED_MILLI_G {
+            Orientation::Vertical
+        } else {
+            Orientation::Tilted
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(x: i16, y: i16, z: i16) -> AccelerometerResponse {
+        AccelerometerResponse {
+            timestamp: 0,
+            x,
+            y,
+            z,
+        }
+    }
+
+    #[test]
+    fn can_parse_golden_packet() {
+        let response = AccelerometerResponse::new(Message {
+            data: vec![
+                0x10, 0x01, 0x0A, 0x00, 0x00, 0x01, 0xF4, 0xFF, 0xCE, 0x00, 0x14, 0x03, 0xE8, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        })
+        .unwrap();
+        assert_eq!(response.timestamp, 500);
+        assert_eq!(response.x, -50);
+        assert_eq!(response.y, 20);
+        assert_eq!(response.z, 1000);
+    }
+
+    #[test]
+    fn can_tell_orientation() {
+        assert_eq!(
+            reading(-50, 20, 1000).orientation(),
+            Orientation::Horizontal
+        );
+        assert_eq!(reading(10, 0, -990).orientation(), Orientation::Horizontal);
+        assert_eq!(reading(0, -1000, 30).orientation(), Orientation::Vertical);
+        assert_eq!(reading(600, 600, 100).orientation(), Orientation::Vertical);
+        assert_eq!(reading(0, 700, 700).orientation(), Orientation::Tilted);
+    }
+}
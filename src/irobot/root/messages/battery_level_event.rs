@@ -0,0 +1,40 @@
+use super::response_reader::ResponseReader;
+use crate::irobot::root::{Message, RootError};
+
+// Sent by the robot whenever its battery level changes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatteryLevelEvent {
+    pub timestamp: u32,
+    pub voltage: u16, // in mV
+    pub percent: u8,
+}
+
+impl BatteryLevelEvent {
+    pub fn new(message: Message) -> Result<BatteryLevelEvent, RootError> {
+        let mut reader = ResponseReader::new(&message);
+        Ok(BatteryLevelEvent {
+            timestamp: reader.read_u32()?,
+            voltage: reader.read_u16()?,
+            percent: reader.read_u8()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_golden_packet() {
+        let event = BatteryLevelEvent::new(Message {
+            data: vec![
+                0x0E, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x0D, 0xAC, 0x0A, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        })
+        .unwrap();
+        assert_eq!(event.timestamp, 65536);
+        assert_eq!(event.voltage, 3500);
+        assert_eq!(event.percent, 10);
+    }
+}
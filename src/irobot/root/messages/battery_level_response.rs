@@ -0,0 +1,38 @@
+use super::response_reader::ResponseReader;
+use crate::irobot::root::{Message, RootError};
+
+pub struct BatteryLevelResponse {
+    pub timestamp: u32,
+    pub voltage: u16, // in mV
+    pub percent: u8,
+}
+
+impl BatteryLevelResponse {
+    pub fn new(message: Message) -> Result<BatteryLevelResponse, RootError> {
+        let mut reader = ResponseReader::new(&message);
+        Ok(BatteryLevelResponse {
+            timestamp: reader.read_u32()?,
+            voltage: reader.read_u16()?,
+            percent: reader.read_u8()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_golden_packet() {
+        let response = BatteryLevelResponse::new(Message {
+            data: vec![
+                0x0E, 0x01, 0x08, 0x00, 0x00, 0x13, 0x88, 0x0F, 0xA0, 0x4B, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        })
+        .unwrap();
+        assert_eq!(response.timestamp, 5000);
+        assert_eq!(response.voltage, 4000);
+        assert_eq!(response.percent, 75);
+    }
+}
@@ -0,0 +1,42 @@
+use super::response_reader::ResponseReader;
+use crate::irobot::root::{Message, RootError};
+
+// Sent by the robot whenever either side of the front bumper is pressed or released
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BumperEvent {
+    pub timestamp: u32,
+    pub left: bool,
+    pub right: bool,
+}
+
+impl BumperEvent {
+    pub fn new(message: Message) -> Result<BumperEvent, RootError> {
+        let mut reader = ResponseReader::new(&message);
+        let timestamp = reader.read_u32()?;
+        let state = reader.read_u8()?;
+        Ok(BumperEvent {
+            timestamp,
+            left: state & 0x80 != 0,
+            right: state & 0x40 != 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_golden_packet() {
+        let event = BumperEvent::new(Message {
+            data: vec![
+                0x0C, 0x00, 0x03, 0x00, 0x00, 0x03, 0xE8, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        })
+        .unwrap();
+        assert_eq!(event.timestamp, 1000);
+        assert!(event.left);
+        assert!(!event.right);
+    }
+}
@@ -0,0 +1,43 @@
+use super::response_reader::ResponseReader;
+use crate::irobot::root::{Message, RootError};
+
+// Sent by the robot when its cliff sensor finds, or stops finding, an edge in front of it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CliffEvent {
+    pub timestamp: u32,
+    pub cliff: bool,
+    pub sensor: u16,    // in mV
+    pub threshold: u16, // in mV
+}
+
+impl CliffEvent {
+    pub fn new(message: Message) -> Result<CliffEvent, RootError> {
+        let mut reader = ResponseReader::new(&message);
+        Ok(CliffEvent {
+            timestamp: reader.read_u32()?,
+            cliff: reader.read_u8()? != 0,
+            sensor: reader.read_u16()?,
+            threshold: reader.read_u16()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_golden_packet() {
+        let event = CliffEvent::new(Message {
+            data: vec![
+                0x14, 0x00, 0x06, 0x00, 0x00, 0x07, 0xD0, 0x01, 0x00, 0x96, 0x01, 0x2C, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        })
+        .unwrap();
+        assert_eq!(event.timestamp, 2000);
+        assert!(event.cliff);
+        assert_eq!(event.sensor, 150);
+        assert_eq!(event.threshold, 300);
+    }
+}
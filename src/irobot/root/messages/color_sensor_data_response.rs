@@ -0,0 +1,38 @@
+use super::response_reader::ResponseReader;
+use crate::irobot::root::{Message, RootError};
+
+// Readings from one bank of 8 color sensors, in the format and lighting that was asked for
+pub struct ColorSensorDataResponse {
+    pub values: [u16; 8],
+}
+
+impl ColorSensorDataResponse {
+    pub fn new(message: Message) -> Result<ColorSensorDataResponse, RootError> {
+        let mut reader = ResponseReader::new(&message);
+        let mut values = [0; 8];
+        for value in values.iter_mut() {
+            *value = reader.read_u16()?;
+        }
+        Ok(ColorSensorDataResponse { values })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_golden_packet() {
+        let response = ColorSensorDataResponse::new(Message {
+            data: vec![
+                0x04, 0x01, 0x09, 0x00, 0x00, 0x00, 0x01, 0x00, 0xFF, 0x01, 0x00, 0x0F, 0xFF, 0x08,
+                0x00, 0x0A, 0xBC, 0x00, 0x7F, 0x00,
+            ],
+        })
+        .unwrap();
+        assert_eq!(
+            response.values,
+            [0x0000, 0x0001, 0x00FF, 0x0100, 0x0FFF, 0x0800, 0x0ABC, 0x007F]
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use super::response_reader::ResponseReader;
+use crate::irobot::root::{Message, RootError};
+
+// Number of color sensors along the bottom of the robot
+pub const COLOR_SENSOR_COUNT: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    White,
+    Black,
+    Red,
+    Green,
+    Blue,
+    Unknown(u8),
+}
+
+impl Color {
+    fn from_nibble(nibble: u8) -> Color {
+        match nibble {
+            0 => Color::White,
+            1 => Color::Black,
+            2 => Color::Red,
+            3 => Color::Green,
+            4 => Color::Blue,
+            other => Color::Unknown(other),
+        }
+    }
+}
+
+// Sent by the robot whenever the colors seen under it change. Sensors are numbered from the robot's left
+// to its right.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColorSensorEvent {
+    pub colors: [Color; COLOR_SENSOR_COUNT],
+}
+
+impl ColorSensorEvent {
+    pub fn new(message: Message) -> Result<ColorSensorEvent, RootError> {
+        let mut reader = ResponseReader::new(&message);
+        let mut colors = [Color::White; COLOR_SENSOR_COUNT];
+
+        // Two sensors are packed into each byte, the first in the upper nibble
+        for pair in colors.chunks_mut(2) {
+            let byte = reader.read_u8()?;
+            pair[0] = Color::from_nibble(byte >> 4);
+            pair[1] = Color::from_nibble(byte & 0x0F);
+        }
+        Ok(ColorSensorEvent { colors })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_golden_packet() {
+        let event = ColorSensorEvent::new(Message {
+            data: vec![
+                0x04, 0x02, 0x00, 0x01, 0x23, 0x4F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x11, 0x00,
+            ],
+        })
+        .unwrap();
+        assert_eq!(
+            &event.colors[0..6],
+            &[
+                Color::White,
+                Color::Black,
+                Color::Red,
+                Color::Green,
+                Color::Blue,
+                Color::Unknown(0x0F)
+            ]
+        );
+        assert_eq!(event.colors[29], Color::White);
+        assert_eq!(event.colors[30], Color::Black);
+        assert_eq!(event.colors[31], Color::Black);
+    }
+}
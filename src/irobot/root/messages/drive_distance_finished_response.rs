@@ -1,4 +1,5 @@
-use crate::irobot::root::Message;
+use super::response_reader::ResponseReader;
+use crate::irobot::root::{Message, RootError};
 
 pub struct DriveDistanceFinishedResponse {
     pub timestamp: u32,
@@ -8,12 +9,33 @@ pub struct DriveDistanceFinishedResponse {
 }
 
 impl DriveDistanceFinishedResponse {
-    pub fn new(message: Message) -> DriveDistanceFinishedResponse {
-        DriveDistanceFinishedResponse {
-            timestamp: u32::from_ne_bytes(message.data[3..7].try_into().unwrap()),
-            x_coord: i32::from_ne_bytes(message.data[7..11].try_into().unwrap()),
-            y_coord: i32::from_ne_bytes(message.data[11..15].try_into().unwrap()),
-            heading: i16::from_ne_bytes(message.data[15..17].try_into().unwrap()),
-        }
+    pub fn new(message: Message) -> Result<DriveDistanceFinishedResponse, RootError> {
+        let mut reader = ResponseReader::new(&message);
+        Ok(DriveDistanceFinishedResponse {
+            timestamp: reader.read_u32()?,
+            x_coord: reader.read_i32()?,
+            y_coord: reader.read_i32()?,
+            heading: reader.read_i16()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_golden_packet() {
+        let response = DriveDistanceFinishedResponse::new(Message {
+            data: vec![
+                0x01, 0x08, 0x05, 0x00, 0x00, 0x13, 0x88, 0xFF, 0xFF, 0xFF, 0xE7, 0x00, 0x00, 0x01,
+                0x2C, 0x07, 0x08, 0x00, 0x00, 0x00,
+            ],
+        })
+        .unwrap();
+        assert_eq!(response.timestamp, 5000);
+        assert_eq!(response.x_coord, -25);
+        assert_eq!(response.y_coord, 300);
+        assert_eq!(response.heading, 1800);
     }
 }
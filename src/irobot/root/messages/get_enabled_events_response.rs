@@ -0,0 +1,41 @@
+use super::response_reader::ResponseReader;
+use crate::irobot::root::{Message, RootDeviceId, RootError};
+
+// Which devices the robot sends events for, as a bitfield with device 0 in the lowest bit of the last byte
+pub struct GetEnabledEventsResponse {
+    pub devices: [u8; 16],
+}
+
+impl GetEnabledEventsResponse {
+    pub fn new(message: Message) -> Result<GetEnabledEventsResponse, RootError> {
+        let mut reader = ResponseReader::new(&message);
+        Ok(GetEnabledEventsResponse {
+            devices: reader.read_bytes()?,
+        })
+    }
+
+    pub fn is_enabled(&self, device: RootDeviceId) -> bool {
+        let device = device as usize;
+        self.devices[15 - device / 8] & (1 << (device % 8)) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_golden_packet() {
+        let response = GetEnabledEventsResponse::new(Message {
+            data: vec![
+                0x00, 0x0B, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x02, 0x00, 0x10, 0x00,
+            ],
+        })
+        .unwrap();
+        assert!(response.is_enabled(RootDeviceId::ColorSensor));
+        assert!(response.is_enabled(RootDeviceId::TouchSensors));
+        assert!(!response.is_enabled(RootDeviceId::Bumpers));
+        assert!(!response.is_enabled(RootDeviceId::General));
+    }
+}
@@ -0,0 +1,32 @@
+use super::response_reader::ResponseReader;
+use crate::irobot::root::{Message, RootError};
+
+pub struct GetNameResponse {
+    pub name: String,
+}
+
+impl GetNameResponse {
+    pub fn new(message: Message) -> Result<GetNameResponse, RootError> {
+        let mut reader = ResponseReader::new(&message);
+        Ok(GetNameResponse {
+            name: reader.read_string::<16>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_golden_packet() {
+        let response = GetNameResponse::new(Message {
+            data: vec![
+                0x00, 0x02, 0x04, 0x52, 0x6F, 0x6F, 0x74, 0x20, 0x32, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        })
+        .unwrap();
+        assert_eq!(response.name, "Root 2");
+    }
+}
@@ -0,0 +1,41 @@
+use super::response_reader::ResponseReader;
+use crate::irobot::root::{Message, RootError};
+
+pub struct GetPositionResponse {
+    pub timestamp: u32,
+    pub x_coord: i32,
+    pub y_coord: i32,
+    pub heading: i16,
+}
+
+impl GetPositionResponse {
+    pub fn new(message: Message) -> Result<GetPositionResponse, RootError> {
+        let mut reader = ResponseReader::new(&message);
+        Ok(GetPositionResponse {
+            timestamp: reader.read_u32()?,
+            x_coord: reader.read_i32()?,
+            y_coord: reader.read_i32()?,
+            heading: reader.read_i16()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_golden_packet() {
+        let response = GetPositionResponse::new(Message {
+            data: vec![
+                0x01, 0x10, 0x05, 0x00, 0x00, 0x13, 0x88, 0xFF, 0xFF, 0xFF, 0xE7, 0x00, 0x00, 0x01,
+                0x2C, 0x07, 0x08, 0x00, 0x00, 0x00,
+            ],
+        })
+        .unwrap();
+        assert_eq!(response.timestamp, 5000);
+        assert_eq!(response.x_coord, -25);
+        assert_eq!(response.y_coord, 300);
+        assert_eq!(response.heading, 1800);
+    }
+}
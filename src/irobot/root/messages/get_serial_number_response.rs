@@ -0,0 +1,32 @@
+use super::response_reader::ResponseReader;
+use crate::irobot::root::{Message, RootError};
+
+pub struct GetSerialNumberResponse {
+    pub serial_number: String,
+}
+
+impl GetSerialNumberResponse {
+    pub fn new(message: Message) -> Result<GetSerialNumberResponse, RootError> {
+        let mut reader = ResponseReader::new(&message);
+        Ok(GetSerialNumberResponse {
+            serial_number: reader.read_string::<12>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_golden_packet() {
+        let response = GetSerialNumberResponse::new(Message {
+            data: vec![
+                0x00, 0x0E, 0x07, 0x52, 0x54, 0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38,
+                0x39, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        })
+        .unwrap();
+        assert_eq!(response.serial_number, "RT0123456789");
+    }
+}
@@ -0,0 +1,32 @@
+use super::response_reader::ResponseReader;
+use crate::irobot::root::{Message, RootError};
+
+pub struct GetSkuResponse {
+    pub sku: String,
+}
+
+impl GetSkuResponse {
+    pub fn new(message: Message) -> Result<GetSkuResponse, RootError> {
+        let mut reader = ResponseReader::new(&message);
+        Ok(GetSkuResponse {
+            sku: reader.read_string::<16>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_golden_packet() {
+        let response = GetSkuResponse::new(Message {
+            data: vec![
+                0x00, 0x0F, 0x09, 0x52, 0x54, 0x30, 0x2D, 0x53, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        })
+        .unwrap();
+        assert_eq!(response.sku, "RT0-S");
+    }
+}
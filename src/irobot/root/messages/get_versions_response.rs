@@ -1,4 +1,5 @@
-use crate::irobot::root::Message;
+use super::response_reader::ResponseReader;
+use crate::irobot::root::{Message, RootError};
 
 pub struct GetVersionsResponse {
     pub board_id: u8,
@@ -14,18 +15,43 @@ pub struct GetVersionsResponse {
 }
 
 impl GetVersionsResponse {
-    pub fn new(message: Message) -> GetVersionsResponse {
-        GetVersionsResponse {
-            board_id: message.data[3],
-            firmware_major_version: message.data[4],
-            firmware_minor_version: message.data[5],
-            hardware_major_version: message.data[6],
-            hardware_minor_version: message.data[7],
-            bootloader_major_version: message.data[8],
-            bootloader_minor_version: message.data[9],
-            protocol_major_version: message.data[10],
-            protocol_minor_version: message.data[11],
-            patch_number: message.data[12],
-        }
+    pub fn new(message: Message) -> Result<GetVersionsResponse, RootError> {
+        let mut reader = ResponseReader::new(&message);
+        Ok(GetVersionsResponse {
+            board_id: reader.read_u8()?,
+            firmware_major_version: reader.read_u8()?,
+            firmware_minor_version: reader.read_u8()?,
+            hardware_major_version: reader.read_u8()?,
+            hardware_minor_version: reader.read_u8()?,
+            bootloader_major_version: reader.read_u8()?,
+            bootloader_minor_version: reader.read_u8()?,
+            protocol_major_version: reader.read_u8()?,
+            protocol_minor_version: reader.read_u8()?,
+            patch_number: reader.read_u8()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_golden_packet() {
+        let response = GetVersionsResponse::new(Message {
+            data: vec![
+                0x00, 0x00, 0x01, 0xA5, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x04, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        })
+        .unwrap();
+        assert_eq!(response.board_id, 0xA5);
+        assert_eq!(response.firmware_major_version, 1);
+        assert_eq!(response.firmware_minor_version, 0);
+        assert_eq!(response.hardware_major_version, 1);
+        assert_eq!(response.bootloader_major_version, 1);
+        assert_eq!(response.protocol_major_version, 1);
+        assert_eq!(response.protocol_minor_version, 4);
+        assert_eq!(response.patch_number, 0);
     }
 }
@@ -0,0 +1,58 @@
+use super::response_reader::ResponseReader;
+use crate::irobot::root::{Message, RootError};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightState {
+    BothDark,
+    RightBrighter,
+    LeftBrighter,
+    BothBright,
+    Unknown(u8),
+}
+
+// Sent by the robot whenever the ambient light reaching its two eyes changes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LightEvent {
+    pub timestamp: u32,
+    pub state: LightState,
+    pub left_ambient: u16,  // in decipercent
+    pub right_ambient: u16, // in decipercent
+}
+
+impl LightEvent {
+    pub fn new(message: Message) -> Result<LightEvent, RootError> {
+        let mut reader = ResponseReader::new(&message);
+        Ok(LightEvent {
+            timestamp: reader.read_u32()?,
+            state: match reader.read_u8()? {
+                4 => LightState::BothDark,
+                5 => LightState::RightBrighter,
+                6 => LightState::LeftBrighter,
+                7 => LightState::BothBright,
+                other => LightState::Unknown(other),
+            },
+            left_ambient: reader.read_u16()?,
+            right_ambient: reader.read_u16()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_golden_packet() {
+        let event = LightEvent::new(Message {
+            data: vec![
+                0x0D, 0x00, 0x05, 0x00, 0x00, 0x00, 0x64, 0x06, 0x02, 0xEE, 0x00, 0x32, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        })
+        .unwrap();
+        assert_eq!(event.timestamp, 100);
+        assert_eq!(event.state, LightState::LeftBrighter);
+        assert_eq!(event.left_ambient, 750);
+        assert_eq!(event.right_ambient, 50);
+    }
+}
@@ -1,13 +1,32 @@
-use crate::irobot::root::Message;
+use super::response_reader::ResponseReader;
+use crate::irobot::root::{Message, RootError};
 
 pub struct MarkerFinishedResponse {
     pub position: u8,
 }
 
 impl MarkerFinishedResponse {
-    pub fn new(message: Message) -> MarkerFinishedResponse {
-        MarkerFinishedResponse {
-            position: message.data[3],
-        }
+    pub fn new(message: Message) -> Result<MarkerFinishedResponse, RootError> {
+        let mut reader = ResponseReader::new(&message);
+        Ok(MarkerFinishedResponse {
+            position: reader.read_u8()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_golden_packet() {
+        let response = MarkerFinishedResponse::new(Message {
+            data: vec![
+                0x02, 0x00, 0x07, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        })
+        .unwrap();
+        assert_eq!(response.position, 0x01);
     }
 }
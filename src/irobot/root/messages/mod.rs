@@ -1,6 +1,20 @@
+mod response_reader;
+
 mod get_versions_response;
 pub use self::get_versions_response::GetVersionsResponse;
 
+mod get_name_response;
+pub use self::get_name_response::GetNameResponse;
+
+mod get_enabled_events_response;
+pub use self::get_enabled_events_response::GetEnabledEventsResponse;
+
+mod get_serial_number_response;
+pub use self::get_serial_number_response::GetSerialNumberResponse;
+
+mod get_sku_response;
+pub use self::get_sku_response::GetSkuResponse;
+
 mod drive_distance_finished_response;
 pub use self::drive_distance_finished_response::DriveDistanceFinishedResponse;
 
@@ -12,3 +26,45 @@ pub use self::marker_finished_response::MarkerFinishedResponse;
 
 mod drive_arc_finished_response;
 pub use self::drive_arc_finished_response::DriveArcFinishedResponse;
+
+mod get_position_response;
+pub use self::get_position_response::GetPositionResponse;
+
+mod navigate_to_position_finished_response;
+pub use self::navigate_to_position_finished_response::NavigateToPositionFinishedResponse;
+
+mod motor_stall_event;
+pub use self::motor_stall_event::MotorStallEvent;
+pub use self::motor_stall_event::StallCause;
+pub use self::motor_stall_event::StalledMotor;
+
+mod color_sensor_data_response;
+pub use self::color_sensor_data_response::ColorSensorDataResponse;
+
+mod color_sensor_event;
+pub use self::color_sensor_event::Color;
+pub use self::color_sensor_event::ColorSensorEvent;
+pub use self::color_sensor_event::COLOR_SENSOR_COUNT;
+
+mod bumper_event;
+pub use self::bumper_event::BumperEvent;
+
+mod light_event;
+pub use self::light_event::LightEvent;
+pub use self::light_event::LightState;
+
+mod touch_sensor_event;
+pub use self::touch_sensor_event::TouchSensorEvent;
+
+mod cliff_event;
+pub use self::cliff_event::CliffEvent;
+
+mod battery_level_response;
+pub use self::battery_level_response::BatteryLevelResponse;
+
+mod battery_level_event;
+pub use self::battery_level_event::BatteryLevelEvent;
+
+mod accelerometer_response;
+pub use self::accelerometer_response::AccelerometerResponse;
+pub use self::accelerometer_response::Orientation;
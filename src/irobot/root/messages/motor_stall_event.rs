@@ -0,0 +1,82 @@
+use super::response_reader::ResponseReader;
+use crate::irobot::root::{Message, RootError};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StalledMotor {
+    Left,
+    Right,
+    MarkerEraser,
+    Unknown(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StallCause {
+    NoStall,
+    Overcurrent,
+    Undercurrent,
+    Underspeed,
+    SaturatedPid,
+    Timeout,
+    Unknown(u8),
+}
+
+// Sent by the robot whenever one of its motors stalls
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MotorStallEvent {
+    pub timestamp: u32,
+    pub motor: StalledMotor,
+    pub cause: StallCause,
+}
+
+impl MotorStallEvent {
+    pub fn new(message: Message) -> Result<MotorStallEvent, RootError> {
+        let mut reader = ResponseReader::new(&message);
+        Ok(MotorStallEvent {
+            timestamp: reader.read_u32()?,
+            motor: match reader.read_u8()? {
+                0 => StalledMotor::Left,
+                1 => StalledMotor::Right,
+                2 => StalledMotor::MarkerEraser,
+                other => StalledMotor::Unknown(other),
+            },
+            cause: match reader.read_u8()? {
+                0 => StallCause::NoStall,
+                1 => StallCause::Overcurrent,
+                2 => StallCause::Undercurrent,
+                3 => StallCause::Underspeed,
+                4 => StallCause::SaturatedPid,
+                5 => StallCause::Timeout,
+                other => StallCause::Unknown(other),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_golden_packet() {
+        let event = MotorStallEvent::new(Message {
+            data: vec![
+                0x01, 0x1D, 0x00, 0x00, 0x00, 0x27, 0x10, 0x01, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        })
+        .unwrap();
+        assert_eq!(event.timestamp, 10000);
+        assert_eq!(event.motor, StalledMotor::Right);
+        assert_eq!(event.cause, StallCause::Underspeed);
+    }
+
+    #[test]
+    fn keeps_unknown_values() {
+        let event = MotorStallEvent::new(Message {
+            data: vec![0x01, 0x1D, 0x00, 0x00, 0x00, 0x00, 0x00, 0x09, 0x0A],
+        })
+        .unwrap();
+        assert_eq!(event.motor, StalledMotor::Unknown(0x09));
+        assert_eq!(event.cause, StallCause::Unknown(0x0A));
+    }
+}
@@ -0,0 +1,103 @@
+use crate::irobot::root::{Message, RootError};
+
+// Reads the fields of a response payload in order. The Root protocol is big-endian throughout, and every
+// read checks the packet is long enough so short data comes back as an error rather than a panic.
+pub(crate) struct ResponseReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ResponseReader<'a> {
+    // The payload starts after the device, command and id bytes
+    pub fn new(message: &'a Message) -> ResponseReader<'a> {
+        ResponseReader {
+            data: &message.data,
+            offset: 3,
+        }
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], RootError> {
+        let bytes = self.data.get(self.offset..self.offset + N).ok_or_else(|| {
+            RootError::MalformedResponse(format!(
+                "needed {} bytes at offset {} but packet is only {} bytes",
+                N,
+                self.offset,
+                self.data.len()
+            ))
+        })?;
+        self.offset += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, RootError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, RootError> {
+        Ok(u16::from_be_bytes(self.take()?))
+    }
+
+    pub fn read_i16(&mut self) -> Result<i16, RootError> {
+        Ok(i16::from_be_bytes(self.take()?))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, RootError> {
+        Ok(u32::from_be_bytes(self.take()?))
+    }
+
+    pub fn read_i32(&mut self) -> Result<i32, RootError> {
+        Ok(i32::from_be_bytes(self.take()?))
+    }
+
+    pub fn read_bytes<const N: usize>(&mut self) -> Result<[u8; N], RootError> {
+        self.take()
+    }
+
+    // Text is sent as UTF-8 padded out with zeros to a fixed length
+    pub fn read_string<const N: usize>(&mut self) -> Result<String, RootError> {
+        let bytes = self.take::<N>()?;
+        let end = bytes.iter().position(|byte| *byte == 0x00).unwrap_or(N);
+        Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_fields_big_endian() {
+        let message = Message {
+            data: vec![
+                0x01, 0x08, 0x05, 0x12, 0x34, 0x56, 0x78, 0xFF, 0xFF, 0xFF, 0xFE, 0x03, 0x84, 0x7F,
+            ],
+        };
+        let mut reader = ResponseReader::new(&message);
+        assert_eq!(reader.read_u32().unwrap(), 0x12345678);
+        assert_eq!(reader.read_i32().unwrap(), -2);
+        assert_eq!(reader.read_i16().unwrap(), 900);
+        assert_eq!(reader.read_u8().unwrap(), 0x7F);
+    }
+
+    #[test]
+    fn reads_zero_padded_strings() {
+        let message = Message {
+            data: vec![0x00, 0x02, 0x05, b'R', b'o', b'o', b't', 0x00, 0x00, 0x01],
+        };
+        let mut reader = ResponseReader::new(&message);
+        assert_eq!(reader.read_string::<6>().unwrap(), "Root");
+        assert_eq!(reader.read_bytes::<1>().unwrap(), [0x01]);
+    }
+
+    #[test]
+    fn short_data_is_an_error() {
+        let message = Message {
+            data: vec![0x01, 0x08, 0x05, 0x00, 0x00],
+        };
+        let mut reader = ResponseReader::new(&message);
+        assert!(matches!(
+            reader.read_u32(),
+            Err(RootError::MalformedResponse(_))
+        ));
+    }
+}
@@ -0,0 +1,48 @@
+use super::response_reader::ResponseReader;
+use crate::irobot::root::{Message, RootError};
+
+// Sent by the robot whenever any of the four touch sensors on its top are pressed or released
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TouchSensorEvent {
+    pub timestamp: u32,
+    pub front_left: bool,
+    pub front_right: bool,
+    pub rear_right: bool,
+    pub rear_left: bool,
+}
+
+impl TouchSensorEvent {
+    pub fn new(message: Message) -> Result<TouchSensorEvent, RootError> {
+        let mut reader = ResponseReader::new(&message);
+        let timestamp = reader.read_u32()?;
+        let state = reader.read_u8()?;
+        Ok(TouchSensorEvent {
+            timestamp,
+            front_left: state & 0x80 != 0,
+            front_right: state & 0x40 != 0,
+            rear_right: state & 0x20 != 0,
+            rear_left: state & 0x10 != 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_golden_packet() {
+        let event = TouchSensorEvent::new(Message {
+            data: vec![
+                0x11, 0x00, 0x04, 0x00, 0x01, 0x00, 0x00, 0x60, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        })
+        .unwrap();
+        assert_eq!(event.timestamp, 65536);
+        assert!(!event.front_left);
+        assert!(event.front_right);
+        assert!(event.rear_right);
+        assert!(!event.rear_left);
+    }
+}
@@ -1,8 +1,41 @@
-mod messages;
+pub mod messages;
+pub mod transport;
+
+mod melody;
+pub use self::melody::Melody;
+pub use self::melody::MelodyStep;
+
+mod root_error;
+pub use self::root_error::RootError;
+
+mod root_event;
+pub use self::root_event::RootEvent;
 
 mod root_robot;
+#[cfg(test)]
+pub(crate) use self::root_robot::build_checked_packet;
+pub use self::root_robot::is_root_advertisement;
 pub use self::root_robot::is_root_robot;
+pub use self::root_robot::ColorSensorFormat;
+pub use self::root_robot::ColorSensorLighting;
+pub use self::root_robot::GravityCompensation;
 pub use self::root_robot::LEDLightsState;
 pub use self::root_robot::MarkerPosition;
 pub use self::root_robot::Message;
+pub use self::root_robot::ReconnectPolicy;
+pub use self::root_robot::RootDeviceId;
 pub use self::root_robot::RootRobot;
+pub use self::root_robot::ShutdownHandle;
+pub use self::root_robot::MAX_MOTOR_SPEED_MM_PER_SEC;
+pub(crate) use self::root_robot::ROOT_IDENTIFIER_UUID;
+
+mod safety_policy;
+pub use self::safety_policy::DefaultSafetyPolicy;
+pub use self::safety_policy::SafetyAction;
+pub use self::safety_policy::SafetyPolicy;
+
+mod simulated_root;
+pub use self::simulated_root::spawn_message_loop;
+pub use self::simulated_root::SimulatedPose;
+pub use self::simulated_root::SimulatedRoot;
+pub use self::simulated_root::TrailSegment;
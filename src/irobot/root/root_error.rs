@@ -0,0 +1,41 @@
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::utils::MessageWaitError;
+
+// Everything that can go wrong talking to a Root robot
+#[derive(Debug, Error)]
+pub enum RootError {
+    #[error("Bluetooth transport failed: {0}")]
+    Transport(#[from] btleplug::Error),
+    #[error("Robot is not connected")]
+    Disconnected,
+    // The command was sent, so the robot may well have carried it out
+    #[error("Connection lost before the robot answered")]
+    ConnectionLost,
+    #[error("No Root robot found")]
+    NotFound,
+    #[error("Timed out after {0:?} waiting for a response")]
+    TimedOut(Duration),
+    #[error("Response was taken by another command")]
+    Superseded,
+    #[error("Packet failed CRC check")]
+    CrcMismatch,
+    #[error("Malformed response: {0}")]
+    MalformedResponse(String),
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("Battery at {available}% but about {needed}% is needed")]
+    InsufficientBattery { available: u8, needed: u8 },
+    #[error("Robot stopped for safety: {0}")]
+    SafetyStop(String),
+}
+
+impl From<MessageWaitError> for RootError {
+    fn from(error: MessageWaitError) -> Self {
+        match error {
+            MessageWaitError::TimedOut(timeout) => RootError::TimedOut(timeout),
+            MessageWaitError::Superseded => RootError::Superseded,
+        }
+    }
+}
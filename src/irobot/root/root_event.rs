@@ -0,0 +1,82 @@
+use super::messages::{
+    BatteryLevelEvent, BumperEvent, CliffEvent, ColorSensorEvent, LightEvent, MotorStallEvent,
+    TouchSensorEvent,
+};
+use super::{Message, RootError};
+
+// Something the robot reported on its own rather than in response to a command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RootEvent {
+    MotorStall(MotorStallEvent),
+    ColorSensor(ColorSensorEvent),
+    Bumper(BumperEvent),
+    Light(LightEvent),
+    BatteryLevel(BatteryLevelEvent),
+    TouchSensor(TouchSensorEvent),
+    Cliff(CliffEvent),
+}
+
+impl RootEvent {
+    // Decode a packet if it is one of the events we know about, or None if it is a response to be stored
+    pub(crate) fn decode(data: &[u8]) -> Option<Result<RootEvent, RootError>> {
+        let message = Message {
+            data: data.to_vec(),
+        };
+
+        match (data[0], data[1]) {
+            // Motors - Motor Stall Event
+            (0x01, 0x1D) => Some(MotorStallEvent::new(message).map(RootEvent::MotorStall)),
+            // Color Sensor - Color Sensor Event
+            (0x04, 0x02) => Some(ColorSensorEvent::new(message).map(RootEvent::ColorSensor)),
+            // Bumpers - Bumper Event
+            (0x0C, 0x00) => Some(BumperEvent::new(message).map(RootEvent::Bumper)),
+            // Light Sensors - Light Event
+            (0x0D, 0x00) => Some(LightEvent::new(message).map(RootEvent::Light)),
+            // Battery - Battery Level Event
+            (0x0E, 0x00) => Some(BatteryLevelEvent::new(message).map(RootEvent::BatteryLevel)),
+            // Touch Sensors - Touch Sensor Event
+            (0x11, 0x00) => Some(TouchSensorEvent::new(message).map(RootEvent::TouchSensor)),
+            // Cliff Sensor - Cliff Event
+            (0x14, 0x00) => Some(CliffEvent::new(message).map(RootEvent::Cliff)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_decode_events() {
+        let bump = RootEvent::decode(&[0x0C, 0x00, 0x01, 0x00, 0x00, 0x00, 0x0A, 0xC0]);
+        assert!(matches!(
+            bump,
+            Some(Ok(RootEvent::Bumper(BumperEvent {
+                left: true,
+                right: true,
+                ..
+            })))
+        ));
+
+        let touch = RootEvent::decode(&[0x11, 0x00, 0x01, 0x00, 0x00, 0x00, 0x0A, 0x10]);
+        assert!(matches!(
+            touch,
+            Some(Ok(RootEvent::TouchSensor(TouchSensorEvent {
+                rear_left: true,
+                ..
+            })))
+        ));
+
+        assert!(matches!(
+            RootEvent::decode(&[0x0D, 0x00, 0x01, 0x00]),
+            Some(Err(RootError::MalformedResponse(_)))
+        ));
+    }
+
+    #[test]
+    fn leaves_responses_alone() {
+        assert!(RootEvent::decode(&[0x01, 0x08, 0x01, 0x00]).is_none());
+        assert!(RootEvent::decode(&[0x02, 0x00, 0x01, 0x01]).is_none());
+    }
+}
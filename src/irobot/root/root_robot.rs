@@ -1,34 +1,108 @@
 use super::messages::{
-    DriveArcFinishedResponse, DriveDistanceFinishedResponse, GetVersionsResponse,
-    MarkerFinishedResponse, RotateAngleFinishedResponse,
+    AccelerometerResponse, BatteryLevelResponse, ColorSensorDataResponse, ColorSensorEvent,
+    DriveArcFinishedResponse, DriveDistanceFinishedResponse, GetEnabledEventsResponse,
+    GetNameResponse, GetPositionResponse, GetSerialNumberResponse, GetSkuResponse,
+    GetVersionsResponse, MarkerFinishedResponse, NavigateToPositionFinishedResponse,
+    RotateAngleFinishedResponse,
 };
-use btleplug::api::{CharPropFlags, Peripheral as _, WriteType};
+use super::transport::{NotificationStream, RootTransport};
+use super::{
+    DefaultSafetyPolicy, Melody, MelodyStep, RootError, RootEvent, SafetyAction, SafetyPolicy,
+};
+use btleplug::api::{Peripheral as _, PeripheralProperties, WriteType};
 use btleplug::platform::Peripheral;
 use crc::{Algorithm, Crc};
+use std::collections::HashMap;
+use std::mem::{discriminant, Discriminant};
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+use tokio_stream::wrappers::BroadcastStream;
 use uuid::{uuid, Uuid};
 
-use futures::stream::StreamExt;
+use futures::stream::{Stream, StreamExt};
 
 use crate::utils::MessageStorage;
 
-const ROOT_IDENTIFIER_UUID: Uuid = uuid!("48c5d828-ac2a-442d-97a3-0c9822b04979");
-const ROOT_RX_CHARACTERISTIC: Uuid = uuid!("6e400002-b5a3-f393-e0a9-e50e24dcca9e");
-const ROOT_TX_CHARACTERISTIC: Uuid = uuid!("6e400003-b5a3-f393-e0a9-e50e24dcca9e");
+pub(crate) const ROOT_IDENTIFIER_UUID: Uuid = uuid!("48c5d828-ac2a-442d-97a3-0c9822b04979");
+
+// How long to wait for a response before giving up, unless overridden for a command
+const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Motion commands only respond once the motion is done, so on top of the response timeout
+// we allow for the motion taking place at these (deliberately slow) speeds
+const SLOWEST_DRIVE_SPEED_MM_PER_SEC: f32 = 50.0;
+const SLOWEST_ROTATE_SPEED_DEGREES_PER_SEC: f32 = 45.0;
+
+// Fastest each motor can be told to go, in either direction
+pub const MAX_MOTOR_SPEED_MM_PER_SEC: i32 = 100;
+
+// Largest amount of gravity compensation, in decipercent
+const MAX_GRAVITY_COMPENSATION: u16 = 1000;
+
+// Color sensors are read 8 at a time, in banks 0 to 3 from left to right
+const COLOR_SENSOR_BANKS: u8 = 4;
 
+// Loudest the speaker can be set to, in percent
+const MAX_VOLUME_PERCENT: u8 = 100;
+
+// Longest name the robot can be given, in bytes
+const MAX_NAME_BYTES: usize = 16;
+
+// Longest phrase the robot will say in one packet, in bytes
+const MAX_PHRASE_BYTES: usize = 16;
+
+// How often the message loop checks the link is still up, in case the transport drops without telling us
+const CONNECTION_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+// How many events are kept for slow subscribers before the oldest are dropped
+const EVENT_CHANNEL_CAPACITY: usize = 64;
+
+// Only one of the marker and eraser can be down at a time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum MarkerPosition {
     NothingDown = 0x00,
-    MarkerDown = 0x01, //ErasorDown = 0x02,
+    MarkerDown = 0x01,
+    EraserDown = 0x02,
 }
 
+#[derive(Clone, Copy)]
 pub enum RootDeviceId {
     General = 0x00,
     Motors = 0x01,
     Marker = 0x02,
     LEDLights = 0x03,
+    ColorSensor = 0x04,
     Sound = 0x05,
+    Bumpers = 0x0C,
+    LightSensors = 0x0D,
+    Battery = 0x0E,
+    Accelerometer = 0x10,
+    TouchSensors = 0x11,
     CliffSensor = 0x14,
 }
 
+pub enum GravityCompensation {
+    Off = 0x00,
+    On = 0x01,
+    OnWhenMarkerDown = 0x02,
+}
+
+pub enum ColorSensorLighting {
+    Off = 0x00,
+    Red = 0x01,
+    Green = 0x02,
+    Blue = 0x03,
+    All = 0x04,
+}
+
+pub enum ColorSensorFormat {
+    AdcCounts = 0x00, // 12-bit
+    Millivolts = 0x01,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum LEDLightsState {
     Off = 0x00,
     On = 0x01,
@@ -36,7 +110,66 @@ pub enum LEDLightsState {
     Spin = 0x03,
 }
 
-#[derive(PartialEq, Eq, Hash)]
+// Why the robot was stopped for safety, and whether whatever it was doing can carry on afterwards
+#[derive(Clone)]
+struct SafetyStop {
+    reason: String,
+    resumable: bool,
+}
+
+// How hard to try getting the link back after it drops. Each failed attempt waits twice as long as the
+// last before trying again, up to max_backoff.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    pub attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(8),
+        }
+    }
+}
+
+// Stops the message loop of the robot it came from. Handles can be cloned and handed to other tasks, such
+// as one waiting for Ctrl-C, and stopping through any of them stops the loop for good.
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    shutdown: Arc<watch::Sender<bool>>,
+}
+
+impl ShutdownHandle {
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        *self.shutdown.borrow()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LinkState {
+    Up,
+    // Dropped without being asked to, reconnecting should bring it back
+    Down,
+    // Disconnected on purpose, the message loop stops rather than reconnecting
+    Closed,
+}
+
+// Which connection we are on, counting up each time the link is brought back after a drop
+#[derive(Clone, Copy)]
+struct Connection {
+    session: u32,
+    link: LinkState,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 struct RootMessageKey {
     device: u8,
     command: u8,
@@ -64,7 +197,7 @@ const ROOT_CRC: Crc<u8> = Crc::<u8>::new(&ROOT_CRC_ALGORITHM);
 // The full format requires that the 20th byte is a calculated checksum.
 // This takes the intended packet, pads it to the correct length,
 // then calculates the CRC and adds it.
-fn build_checked_packet(mut packet: Vec<u8>) -> Vec<u8> {
+pub(crate) fn build_checked_packet(mut packet: Vec<u8>) -> Vec<u8> {
     // Assumes the payload is a valid unsigned payload
     assert!(packet.len() < 20);
 
@@ -77,7 +210,76 @@ fn build_checked_packet(mut packet: Vec<u8>) -> Vec<u8> {
     digest.update(&packet);
 
     packet.push(digest.finalize());
-    return packet;
+    packet
+}
+
+// Check a packet received from the robot is the right length and its checksum matches
+pub(crate) fn verify_checked_packet(packet: &[u8]) -> Result<(), RootError> {
+    if packet.len() != 20 {
+        return Err(RootError::MalformedResponse(format!(
+            "Expected 20 byte packet, got {} bytes",
+            packet.len()
+        )));
+    }
+
+    if ROOT_CRC.checksum(&packet[0..19]) != packet[19] {
+        return Err(RootError::CrcMismatch);
+    }
+
+    Ok(())
+}
+
+fn check_motor_speed(speed: i32) -> Result<(), RootError> {
+    if speed.abs() > MAX_MOTOR_SPEED_MM_PER_SEC {
+        return Err(RootError::InvalidArgument(format!(
+            "Motor speed {} is outside +/-{}mm/s",
+            speed, MAX_MOTOR_SPEED_MM_PER_SEC
+        )));
+    }
+    Ok(())
+}
+
+// Stop and Reset and lifting the marker are always allowed through, they are how we leave the robot safe
+fn allowed_during_safety_stop(packet: &[u8]) -> bool {
+    match packet {
+        [device, 0x03, ..] => *device == RootDeviceId::General as u8,
+        [device, 0x00, _, position, ..] => {
+            *device == RootDeviceId::Marker as u8 && *position == MarkerPosition::NothingDown as u8
+        }
+        _ => false,
+    }
+}
+
+// Event enable and disable commands take a 128 bit field of devices, with device 0 in the lowest bit of the last byte
+fn device_bitfield(devices: &[RootDeviceId]) -> [u8; 16] {
+    let mut bitfield = [0u8; 16];
+    for device in devices {
+        let device = *device as usize;
+        bitfield[15 - device / 8] |= 1 << (device % 8);
+    }
+    bitfield
+}
+
+// Break a phrase into pieces short enough to say in one packet, splitting after a space where there is one
+// and never in the middle of a character
+fn split_phrase(phrase: &str) -> Vec<&str> {
+    let mut pieces = vec![];
+    let mut rest = phrase;
+    while rest.len() > MAX_PHRASE_BYTES {
+        let mut end = MAX_PHRASE_BYTES;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if let Some(space) = rest[..end].rfind(' ') {
+            end = space + 1;
+        }
+        pieces.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    if !rest.is_empty() {
+        pieces.push(rest);
+    }
+    pieces
 }
 
 // Root robot defines a specific service to identify it, this checks for that UUID.
@@ -88,31 +290,105 @@ pub fn is_root_robot(peripheral: &Peripheral) -> bool {
         .any(|a| a.uuid == ROOT_IDENTIFIER_UUID)
 }
 
+// Root robots advertise the same service, so they can be picked out while scanning without connecting
+pub fn is_root_advertisement(properties: &PeripheralProperties) -> bool {
+    properties.services.contains(&ROOT_IDENTIFIER_UUID)
+}
+
 pub struct RootRobot {
-    peripheral: Peripheral,
+    transport: Box<dyn RootTransport>,
     message_storage: MessageStorage<RootMessageKey, Message>,
+    response_timeout: Duration,
+    command_timeouts: HashMap<(u8, u8), Duration>,
+    // Set when the robot has been stopped for safety
+    safety_stop: watch::Sender<Option<SafetyStop>>,
+    safety_policy: Box<dyn SafetyPolicy>,
+    // Kind of event which ends a back off pause once it reports the condition has gone
+    resume_on_clear: Mutex<Option<Discriminant<RootEvent>>>,
+    // Number of packets received which were dropped for being corrupt
+    corrupt_packets: AtomicUsize,
+    // Rolling ID stamped on each command so responses can be matched to the command that caused them
+    next_packet_id: AtomicU8,
+    events: broadcast::Sender<RootEvent>,
+    connection: watch::Sender<Connection>,
+    reconnect_policy: ReconnectPolicy,
+    // Held while reconnecting so only one attempt runs at a time
+    reconnecting: tokio::sync::Mutex<()>,
+    // Opened by reconnect before the link is marked as up, so the message loop can't miss the first responses
+    reconnected_notifications: Mutex<Option<NotificationStream>>,
+    // Set once the message loop has been asked to stop
+    shutdown: Arc<watch::Sender<bool>>,
 }
 
 impl RootRobot {
-    pub fn new(peripheral: Peripheral) -> RootRobot {
+    pub fn new(transport: impl RootTransport + 'static) -> RootRobot {
         RootRobot {
-            peripheral: peripheral,
+            transport: Box::new(transport),
             message_storage: MessageStorage::new(),
+            response_timeout: DEFAULT_RESPONSE_TIMEOUT,
+            command_timeouts: HashMap::new(),
+            safety_stop: watch::channel(None).0,
+            safety_policy: Box::new(DefaultSafetyPolicy::default()),
+            resume_on_clear: Mutex::new(None),
+            corrupt_packets: AtomicUsize::new(0),
+            next_packet_id: AtomicU8::new(0),
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            connection: watch::channel(Connection {
+                session: 0,
+                link: LinkState::Up,
+            })
+            .0,
+            reconnect_policy: ReconnectPolicy::default(),
+            reconnecting: tokio::sync::Mutex::new(()),
+            reconnected_notifications: Mutex::new(None),
+            shutdown: Arc::new(watch::channel(false).0),
         }
     }
 
+    // Set how long to wait for any response before giving up
+    pub fn set_response_timeout(&mut self, timeout: Duration) {
+        self.response_timeout = timeout;
+    }
+
+    // Choose how to react to cliffs, bumps and other events, by default only cliffs stop the robot
+    pub fn set_safety_policy(&mut self, policy: impl SafetyPolicy + 'static) {
+        self.safety_policy = Box::new(policy);
+    }
+
+    // Choose how many times and how quickly to try reconnecting after the link drops
+    pub fn set_reconnect_policy(&mut self, policy: ReconnectPolicy) {
+        self.reconnect_policy = policy;
+    }
+
+    // Override how long to wait for the response to a specific command
+    pub fn set_command_timeout(&mut self, device: RootDeviceId, command: u8, timeout: Duration) {
+        self.command_timeouts
+            .insert((device as u8, command), timeout);
+    }
+
     /////////////////////////////////////////
     // Device 0 - General
     /////////////////////////////////////////
 
     // Command 0 - Get Versions
     // Request a response packet with Command 0 and matching ID containing the software and hardware version numbers.
-    pub async fn print_versions(&self) {
-        let versions_cmd = vec![0x00, 0x00, 0x10, 0xA5];
-        self.send_msg(versions_cmd, WriteType::WithResponse).await;
+    pub async fn get_versions(&self) -> Result<GetVersionsResponse, RootError> {
+        let id = self
+            .send_command(
+                RootDeviceId::General,
+                0x00,
+                &[0xA5],
+                WriteType::WithResponse,
+            )
+            .await?;
+        GetVersionsResponse::new(
+            self.wait_for_message(RootDeviceId::General, 0x00, id)
+                .await?,
+        )
+    }
 
-        let version =
-            GetVersionsResponse::new(self.wait_for_message(RootDeviceId::General, 0x00, 0x10));
+    pub async fn print_versions(&self) -> Result<(), RootError> {
+        let version = self.get_versions().await?;
         println!(
             "Firmware version: {}.{}",
             version.firmware_major_version, version.firmware_minor_version
@@ -130,94 +406,353 @@ impl RootRobot {
             version.protocol_major_version, version.protocol_minor_version
         );
         println!("Patch number: {}", version.patch_number);
+        Ok(())
+    }
+
+    // Command 1 - Set Name
+    // Change the name the robot advertises over BLE, up to 16 bytes of UTF-8.
+    pub async fn set_name(&self, name: &str) -> Result<(), RootError> {
+        if name.len() > MAX_NAME_BYTES {
+            return Err(RootError::InvalidArgument(format!(
+                "Name {:?} is longer than {} bytes",
+                name, MAX_NAME_BYTES
+            )));
+        }
+
+        self.send_command(
+            RootDeviceId::General,
+            0x01,
+            name.as_bytes(),
+            WriteType::WithoutResponse,
+        )
+        .await?;
+        Ok(())
+    }
+
+    // Command 2 - Get Name
+    // Request a response packet with Command 2 and matching ID containing the robot's name.
+    pub async fn get_name(&self) -> Result<GetNameResponse, RootError> {
+        let id = self
+            .send_command(RootDeviceId::General, 0x02, &[], WriteType::WithResponse)
+            .await?;
+        GetNameResponse::new(
+            self.wait_for_message(RootDeviceId::General, 0x02, id)
+                .await?,
+        )
     }
 
     // Command 3 - Stop and Reset
     // Immediately stop the robot and cancel any pending actions. (Same as pressing the stop button in the Root Coding app.)
-    pub async fn stop_and_reset(&self) {
-        let stop_cmd = vec![RootDeviceId::General as u8, 0x03];
-        self.send_msg(stop_cmd, WriteType::WithoutResponse).await;
+    pub async fn stop_and_reset(&self) -> Result<(), RootError> {
+        self.send_command(RootDeviceId::General, 0x03, &[], WriteType::WithoutResponse)
+            .await?;
+        Ok(())
+    }
+
+    // Command 4 - Stop Project
+    // Tell the robot the running project has stopped, it goes back to its idle state.
+    pub async fn stop_project(&self) -> Result<(), RootError> {
+        self.send_command(RootDeviceId::General, 0x04, &[], WriteType::WithoutResponse)
+            .await?;
+        Ok(())
+    }
+
+    // Command 6 - Disconnect
+    // Ask the robot to drop the BLE connection itself, unlike disconnect which drops it from our end.
+    // The link is closed on purpose, so it isn't brought back once the robot lets go.
+    pub async fn request_disconnect(&self) -> Result<(), RootError> {
+        self.connection
+            .send_modify(|connection| connection.link = LinkState::Closed);
+        self.send_command(RootDeviceId::General, 0x06, &[], WriteType::WithoutResponse)
+            .await?;
+        Ok(())
+    }
+
+    // Command 7 - Enable Events
+    // Turn on events from the given devices, leaving the rest as they are.
+    pub async fn enable_events(&self, devices: &[RootDeviceId]) -> Result<(), RootError> {
+        self.send_command(
+            RootDeviceId::General,
+            0x07,
+            &device_bitfield(devices),
+            WriteType::WithoutResponse,
+        )
+        .await?;
+        Ok(())
+    }
+
+    // Command 9 - Disable Events
+    // Turn off events from the given devices, leaving the rest as they are.
+    pub async fn disable_events(&self, devices: &[RootDeviceId]) -> Result<(), RootError> {
+        self.send_command(
+            RootDeviceId::General,
+            0x09,
+            &device_bitfield(devices),
+            WriteType::WithoutResponse,
+        )
+        .await?;
+        Ok(())
+    }
+
+    // Command 11 - Get Enabled Events
+    // Request a response packet with Command 11 and matching ID containing which devices have events turned on.
+    pub async fn get_enabled_events(&self) -> Result<GetEnabledEventsResponse, RootError> {
+        let id = self
+            .send_command(RootDeviceId::General, 0x0B, &[], WriteType::WithResponse)
+            .await?;
+        GetEnabledEventsResponse::new(
+            self.wait_for_message(RootDeviceId::General, 0x0B, id)
+                .await?,
+        )
+    }
+
+    // Command 14 - Get Serial Number
+    // Request a response packet with Command 14 and matching ID containing the robot's serial number.
+    pub async fn get_serial_number(&self) -> Result<GetSerialNumberResponse, RootError> {
+        let id = self
+            .send_command(RootDeviceId::General, 0x0E, &[], WriteType::WithResponse)
+            .await?;
+        GetSerialNumberResponse::new(
+            self.wait_for_message(RootDeviceId::General, 0x0E, id)
+                .await?,
+        )
+    }
+
+    // Command 15 - Get SKU
+    // Request a response packet with Command 15 and matching ID containing the robot's product SKU.
+    pub async fn get_sku(&self) -> Result<GetSkuResponse, RootError> {
+        let id = self
+            .send_command(RootDeviceId::General, 0x0F, &[], WriteType::WithResponse)
+            .await?;
+        GetSkuResponse::new(
+            self.wait_for_message(RootDeviceId::General, 0x0F, id)
+                .await?,
+        )
     }
 
     /////////////////////////////////////////
     // Device 1 - Motors
     /////////////////////////////////////////
 
+    // Command 4 - Set Left and Right Motor Speed
+    // Set the linear velocity for the left and right motors in mm/s, between -100 and 100.
+    pub async fn set_motor_speeds(
+        &self,
+        left_speed: i32,
+        right_speed: i32,
+    ) -> Result<(), RootError> {
+        check_motor_speed(left_speed)?;
+        check_motor_speed(right_speed)?;
+
+        let mut payload = left_speed.to_be_bytes().to_vec();
+        payload.extend_from_slice(&right_speed.to_be_bytes());
+        self.send_command(
+            RootDeviceId::Motors,
+            0x04,
+            &payload,
+            WriteType::WithoutResponse,
+        )
+        .await?;
+        Ok(())
+    }
+
+    // Command 6 - Set Left Motor Speed
+    // Set the linear velocity for the left motor only in mm/s, between -100 and 100.
+    pub async fn set_left_motor_speed(&self, speed: i32) -> Result<(), RootError> {
+        check_motor_speed(speed)?;
+        self.send_command(
+            RootDeviceId::Motors,
+            0x06,
+            &speed.to_be_bytes(),
+            WriteType::WithoutResponse,
+        )
+        .await?;
+        Ok(())
+    }
+
+    // Command 7 - Set Right Motor Speed
+    // Set the linear velocity for the right motor only in mm/s, between -100 and 100.
+    pub async fn set_right_motor_speed(&self, speed: i32) -> Result<(), RootError> {
+        check_motor_speed(speed)?;
+        self.send_command(
+            RootDeviceId::Motors,
+            0x07,
+            &speed.to_be_bytes(),
+            WriteType::WithoutResponse,
+        )
+        .await?;
+        Ok(())
+    }
+
     // Command 8 - Drive Distance
     // Drive a set distance in a straight line. Robot sends a Drive Distance Finished response packet with Command 8 and matching ID when finished.
-    pub async fn drive_distance(&self, distance_mm: i32) -> DriveDistanceFinishedResponse {
-        let distance_bytes = distance_mm.to_be_bytes();
-        let drive_cmd = vec![
-            RootDeviceId::Motors as u8,
-            0x08,
-            0x11,
-            distance_bytes[0],
-            distance_bytes[1],
-            distance_bytes[2],
-            distance_bytes[3],
-        ];
-        self.send_msg(drive_cmd, WriteType::WithResponse).await;
+    pub async fn drive_distance(
+        &self,
+        distance_mm: i32,
+    ) -> Result<DriveDistanceFinishedResponse, RootError> {
+        let id = self
+            .send_command(
+                RootDeviceId::Motors,
+                0x08,
+                &distance_mm.to_be_bytes(),
+                WriteType::WithResponse,
+            )
+            .await?;
 
-        return DriveDistanceFinishedResponse::new(self.wait_for_message(
-            RootDeviceId::Motors,
-            0x08,
-            0x11,
-        ));
+        let motion_time = distance_mm.unsigned_abs() as f32 / SLOWEST_DRIVE_SPEED_MM_PER_SEC;
+        DriveDistanceFinishedResponse::new(
+            self.wait_for_motion(RootDeviceId::Motors, 0x08, id, motion_time)
+                .await?,
+        )
     }
 
     // Command 12 - Rotate Angle
     // Rotate in place by a set angle. Robot sends a Rotate Angle Finished response packet with Command 12 and matching ID when finished.
-    pub async fn rotate_angle(&self, angle_deci_degrees: i32) -> RotateAngleFinishedResponse {
-        let degree_bytes = angle_deci_degrees.to_be_bytes();
-        let rotate_cmd = vec![
-            RootDeviceId::Motors as u8,
-            0x0C,
-            0x12,
-            degree_bytes[0],
-            degree_bytes[1],
-            degree_bytes[2],
-            degree_bytes[3],
-        ];
-        self.send_msg(rotate_cmd, WriteType::WithResponse).await;
-        return RotateAngleFinishedResponse::new(self.wait_for_message(
+    pub async fn rotate_angle(
+        &self,
+        angle_deci_degrees: i32,
+    ) -> Result<RotateAngleFinishedResponse, RootError> {
+        let id = self
+            .send_command(
+                RootDeviceId::Motors,
+                0x0C,
+                &angle_deci_degrees.to_be_bytes(),
+                WriteType::WithResponse,
+            )
+            .await?;
+
+        let motion_time =
+            angle_deci_degrees.unsigned_abs() as f32 / 10.0 / SLOWEST_ROTATE_SPEED_DEGREES_PER_SEC;
+        RotateAngleFinishedResponse::new(
+            self.wait_for_motion(RootDeviceId::Motors, 0x0C, id, motion_time)
+                .await?,
+        )
+    }
+
+    // Command 13 - Set Gravity Compensation
+    // Set the amount of correction used during vertical driving and when gravity compensation is active.
+    // Amount is in decipercent between 0 and 1000, the robot defaults to 500.
+    pub async fn set_gravity_compensation(
+        &self,
+        active: GravityCompensation,
+        amount: u16,
+    ) -> Result<(), RootError> {
+        if amount > MAX_GRAVITY_COMPENSATION {
+            return Err(RootError::InvalidArgument(format!(
+                "Gravity compensation {} is more than {}",
+                amount, MAX_GRAVITY_COMPENSATION
+            )));
+        }
+
+        let mut payload = vec![active as u8];
+        payload.extend_from_slice(&amount.to_be_bytes());
+        self.send_command(
             RootDeviceId::Motors,
-            0x0C,
-            0x12,
-        ));
+            0x0D,
+            &payload,
+            WriteType::WithoutResponse,
+        )
+        .await?;
+        Ok(())
     }
 
     // Command 15 - Reset Position
     // Reset the estimated robot location to (0, 0) and orientation to 90 degrees of yaw (pointing in the direction of
     // positive-y on a right-handed xy plane). The robot also resets the position when the Root robot nose button
     // (or Create 3 power button) is pressed, when a Stop and Reset packet is received, and when a new Bluetooth connection is made.
-    pub async fn reset_position(&self) {
-        let reset_cmd = vec![RootDeviceId::Motors as u8, 0xF, 0x00];
-        self.send_msg(reset_cmd, WriteType::WithoutResponse).await;
+    pub async fn reset_position(&self) -> Result<(), RootError> {
+        self.send_command(RootDeviceId::Motors, 0x0F, &[], WriteType::WithoutResponse)
+            .await?;
+        Ok(())
+    }
+
+    // Command 16 - Get Position
+    // Request a response packet with Command 16 and matching ID containing the robot's current estimated position.
+    pub async fn get_position(&self) -> Result<GetPositionResponse, RootError> {
+        let id = self
+            .send_command(RootDeviceId::Motors, 0x10, &[], WriteType::WithResponse)
+            .await?;
+        GetPositionResponse::new(
+            self.wait_for_message(RootDeviceId::Motors, 0x10, id)
+                .await?,
+        )
+    }
+
+    // Command 17 - Navigate to Position
+    // Navigate to a set coordinate in mm, turning to face it then driving in a straight line. Heading is in
+    // decidegrees between 0 and 3599 to turn to once there, or None to leave the robot facing however it arrived.
+    // Robot sends a Navigate to Position Finished response packet with Command 17 and matching ID when finished.
+    pub async fn navigate_to_position(
+        &self,
+        x_coord: i32,
+        y_coord: i32,
+        heading: Option<i16>,
+    ) -> Result<NavigateToPositionFinishedResponse, RootError> {
+        let heading = match heading {
+            Some(heading) if !(0..3600).contains(&heading) => {
+                return Err(RootError::InvalidArgument(format!(
+                    "Heading {} is not between 0 and 3599",
+                    heading
+                )));
+            }
+            Some(heading) => heading,
+            None => -1,
+        };
+
+        // How long this takes depends on where the robot is now, so find out first
+        let start = self.get_position().await?;
+        let distance = ((x_coord - start.x_coord) as f32).hypot((y_coord - start.y_coord) as f32);
+
+        let mut payload = x_coord.to_be_bytes().to_vec();
+        payload.extend_from_slice(&y_coord.to_be_bytes());
+        payload.extend_from_slice(&heading.to_be_bytes());
+        let id = self
+            .send_command(
+                RootDeviceId::Motors,
+                0x11,
+                &payload,
+                WriteType::WithResponse,
+            )
+            .await?;
+
+        // Allow for turning all the way round twice, once to face the target and once to face the heading
+        let motion_time = distance / SLOWEST_DRIVE_SPEED_MM_PER_SEC
+            + 720.0 / SLOWEST_ROTATE_SPEED_DEGREES_PER_SEC;
+        NavigateToPositionFinishedResponse::new(
+            self.wait_for_motion(RootDeviceId::Motors, 0x11, id, motion_time)
+                .await?,
+        )
     }
 
     // Command 27 - Drive Arc
     // Drive the length of an arc defined by a set angle and radius. Robot sends a Drive Arc Finished response packet
     // with Command 27 and matching ID when finished.
-    pub async fn drive_arc(&self, angle: i32, radius: i32) {
-        let angle_bytes = angle.to_be_bytes();
-        let radius_bytes = radius.to_be_bytes();
-
-        let drive_cmd = vec![
-            RootDeviceId::Motors as u8,
-            0x1B,
-            0x1B,
-            angle_bytes[0],
-            angle_bytes[1],
-            angle_bytes[2],
-            angle_bytes[3],
-            radius_bytes[0],
-            radius_bytes[1],
-            radius_bytes[2],
-            radius_bytes[3],
-        ];
-        self.send_msg(drive_cmd, WriteType::WithResponse).await;
+    pub async fn drive_arc(
+        &self,
+        angle: i32,
+        radius: i32,
+    ) -> Result<DriveArcFinishedResponse, RootError> {
+        let mut payload = angle.to_be_bytes().to_vec();
+        payload.extend_from_slice(&radius.to_be_bytes());
+        let id = self
+            .send_command(
+                RootDeviceId::Motors,
+                0x1B,
+                &payload,
+                WriteType::WithResponse,
+            )
+            .await?;
 
-        DriveArcFinishedResponse::new(self.wait_for_message(RootDeviceId::Motors, 0x1B, 0x1B));
+        let arc_length = (angle as f32 / 10.0).to_radians().abs() * radius.unsigned_abs() as f32;
+        DriveArcFinishedResponse::new(
+            self.wait_for_motion(
+                RootDeviceId::Motors,
+                0x1B,
+                id,
+                arc_length / SLOWEST_DRIVE_SPEED_MM_PER_SEC,
+            )
+            .await?,
+        )
     }
 
     /////////////////////////////////////////
@@ -226,180 +761,710 @@ impl RootRobot {
 
     // Command - 0
     // Set the position of the marker/eraser actuator. Robot sends a Marker/Eraser Position Finished packet with Command 0 and matching ID when finished.
-    pub async fn set_marker_position(&self, position: MarkerPosition) {
-        let marker_cmd = vec![RootDeviceId::Marker as u8, 0x00, 0x13, position as u8];
-        self.send_msg(marker_cmd, WriteType::WithResponse).await;
-        MarkerFinishedResponse::new(self.wait_for_message(RootDeviceId::Marker, 0x00, 0x13));
+    pub async fn set_marker_position(
+        &self,
+        position: MarkerPosition,
+    ) -> Result<MarkerFinishedResponse, RootError> {
+        let id = self
+            .send_command(
+                RootDeviceId::Marker,
+                0x00,
+                &[position as u8],
+                WriteType::WithResponse,
+            )
+            .await?;
+        // Lifting the marker is allowed through a safety stop, so it doesn't give up waiting because of one
+        let lifting = position == MarkerPosition::NothingDown;
+        MarkerFinishedResponse::new(
+            self.wait_for_response(RootDeviceId::Marker, 0x00, id, 0.0, !lifting)
+                .await?,
+        )
     }
 
     /////////////////////////////////////////
     // Device 3 - LED Lights
     /////////////////////////////////////////
 
-    // Command 2
-    // Set LED cross animation type and color.
-    pub async fn set_lights(&self, lights_state: LEDLightsState, r: u8, g: u8, b: u8) {
-        let color_cmd = vec![
-            RootDeviceId::LEDLights as u8,
+    // Command 2 - Set LED Animation
+    // Set LED cross animation type and color. This is the only LED command, anything fancier is done by
+    // sending it again over time, see LightAnimator.
+    pub async fn set_lights(
+        &self,
+        lights_state: LEDLightsState,
+        r: u8,
+        g: u8,
+        b: u8,
+    ) -> Result<(), RootError> {
+        self.send_command(
+            RootDeviceId::LEDLights,
             0x02,
-            0x01,
-            lights_state as u8,
-            r,
-            g,
-            b,
-        ];
-        self.send_msg(color_cmd, WriteType::WithoutResponse).await;
+            &[lights_state as u8, r, g, b],
+            WriteType::WithoutResponse,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /////////////////////////////////////////
+    // Device 4 - Color Sensor
+    /////////////////////////////////////////
+
+    // Command 1 - Get Color Sensor Data
+    // Request a response packet with Command 1 and matching ID containing values from one bank of 8 color sensors,
+    // lit and reported as asked. Bank 0 is the leftmost sensors 0 to 7, up to bank 3 with sensors 24 to 31.
+    pub async fn get_color_sensor_data(
+        &self,
+        bank: u8,
+        lighting: ColorSensorLighting,
+        format: ColorSensorFormat,
+    ) -> Result<ColorSensorDataResponse, RootError> {
+        if bank >= COLOR_SENSOR_BANKS {
+            return Err(RootError::InvalidArgument(format!(
+                "Color sensor bank {} is not between 0 and {}",
+                bank,
+                COLOR_SENSOR_BANKS - 1
+            )));
+        }
+
+        let id = self
+            .send_command(
+                RootDeviceId::ColorSensor,
+                0x01,
+                &[bank, lighting as u8, format as u8],
+                WriteType::WithResponse,
+            )
+            .await?;
+        ColorSensorDataResponse::new(
+            self.wait_for_message(RootDeviceId::ColorSensor, 0x01, id)
+                .await?,
+        )
+    }
+
+    // Command 2 - Color Sensor Event
+    // Stream of the colors seen under the robot, sent whenever they change. Just the color events from events().
+    pub fn color_sensor_events(&self) -> impl Stream<Item = ColorSensorEvent> {
+        self.events().filter_map(|event| async move {
+            match event {
+                RootEvent::ColorSensor(event) => Some(event),
+                _ => None,
+            }
+        })
     }
 
     /////////////////////////////////////////
     // Device 5 - Sound
     /////////////////////////////////////////
 
-    // Command 4
-    // Speak a text string in robot language. Robot sends a Say Phrase Finished response packet with Command 4 and matching ID when finished.
-    pub async fn say_phrase(&self, phrase: &str) {
-        let mut phrase_cmd = vec![RootDeviceId::Sound as u8, 0x04, 0x00];
+    // Command 0 - Play Note
+    // Play a frequency in Hz for a duration in ms. Robot sends a Play Note Finished response packet with Command 0
+    // and matching ID when finished.
+    pub async fn play_note(&self, frequency: u32, duration: u16) -> Result<(), RootError> {
+        let mut payload = frequency.to_be_bytes().to_vec();
+        payload.extend_from_slice(&duration.to_be_bytes());
+        let id = self
+            .send_command(RootDeviceId::Sound, 0x00, &payload, WriteType::WithResponse)
+            .await?;
+        self.wait_for_motion(RootDeviceId::Sound, 0x00, id, duration as f32 / 1000.0)
+            .await?;
+        Ok(())
+    }
+
+    // Command 1 - Stop Sound
+    // Immediately stop any playing sound.
+    pub async fn stop_sound(&self) -> Result<(), RootError> {
+        self.send_command(RootDeviceId::Sound, 0x01, &[], WriteType::WithoutResponse)
+            .await?;
+        Ok(())
+    }
 
-        let phrase_bytes = phrase.bytes();
-        assert!(phrase_bytes.len() < 16);
+    // Command 4 - Say Phrase
+    // Speak a text string in robot language. The robot only takes 16 bytes at a time, so longer phrases are split
+    // between words where possible and said one piece after another. Robot sends a Say Phrase Finished response
+    // packet with Command 4 and matching ID when each piece is finished.
+    pub async fn say_phrase(&self, phrase: &str) -> Result<(), RootError> {
+        for piece in split_phrase(phrase) {
+            let id = self
+                .send_command(
+                    RootDeviceId::Sound,
+                    0x04,
+                    piece.as_bytes(),
+                    WriteType::WithResponse,
+                )
+                .await?;
 
-        for (_, byte) in phrase_bytes.enumerate() {
-            phrase_cmd.push(byte);
+            // Nothing useful in the response, just wait until the robot is done talking
+            self.wait_for_message(RootDeviceId::Sound, 0x04, id).await?;
         }
+        Ok(())
+    }
+
+    // Command 5 - Play Sweep
+    // Sweep linearly between two frequencies in milli-Hz over a duration in ms, at a volume between 0 and 255.
+    // Attack, release and modulation are left off. Robot sends a Play Sweep Finished response packet with
+    // Command 5 and matching ID when finished.
+    pub async fn play_sweep(
+        &self,
+        start_frequency: u32,
+        end_frequency: u32,
+        duration: u16,
+        volume: u8,
+    ) -> Result<(), RootError> {
+        let mut payload = start_frequency.to_be_bytes().to_vec();
+        payload.extend_from_slice(&end_frequency.to_be_bytes());
+        payload.extend_from_slice(&duration.to_be_bytes());
+        // Attack, release, volume, modulation type, modulation rate, then play straight away rather than append
+        payload.extend_from_slice(&[0x00, 0x00, volume, 0x00, 0x00, 0x00]);
+        let id = self
+            .send_command(RootDeviceId::Sound, 0x05, &payload, WriteType::WithResponse)
+            .await?;
+        self.wait_for_motion(RootDeviceId::Sound, 0x05, id, duration as f32 / 1000.0)
+            .await?;
+        Ok(())
+    }
 
-        self.send_msg(phrase_cmd, WriteType::WithoutResponse).await;
+    // Command 6 - Set Volume
+    // Set the speaker volume as a percent between 0 and 100.
+    pub async fn set_volume(&self, volume: u8) -> Result<(), RootError> {
+        if volume > MAX_VOLUME_PERCENT {
+            return Err(RootError::InvalidArgument(format!(
+                "Volume {} is more than {}",
+                volume, MAX_VOLUME_PERCENT
+            )));
+        }
 
-        // Ignore response
-        self.wait_for_message(RootDeviceId::Sound, 0x04, 0x00);
+        self.send_command(
+            RootDeviceId::Sound,
+            0x06,
+            &[volume],
+            WriteType::WithoutResponse,
+        )
+        .await?;
+        Ok(())
     }
 
-    // Print out chracteristics from the robot
-    pub async fn print_characteristics(&self) {
-        println!(
-            "Discover root peripheral {:?} services...",
-            self.peripheral.id()
-        );
-        for service in self.peripheral.services() {
-            println!(
-                "Service UUID {}, primary: {}",
-                service.uuid, service.primary
-            );
-            for characteristic in service.characteristics {
-                println!("  {:?}", characteristic);
+    // Play each note of a melody in turn, staying quiet for the rests
+    pub async fn play_melody(&self, melody: &Melody) -> Result<(), RootError> {
+        if melody.tempo == 0 {
+            return Err(RootError::InvalidArgument(
+                "Melody tempo must be more than 0".to_string(),
+            ));
+        }
+
+        for step in &melody.steps {
+            match *step {
+                MelodyStep::Note { frequency, beats } => {
+                    let duration = melody.beats_duration(beats).as_millis();
+                    let duration = u16::try_from(duration).map_err(|_| {
+                        RootError::InvalidArgument(format!(
+                            "Note of {} beats lasts longer than {} ms",
+                            beats,
+                            u16::MAX
+                        ))
+                    })?;
+                    self.play_note(frequency, duration).await?;
+                }
+                MelodyStep::Rest { beats } => {
+                    tokio::time::sleep(melody.beats_duration(beats)).await;
+                }
             }
         }
+        Ok(())
+    }
+
+    /////////////////////////////////////////
+    // Device 14 - Battery
+    /////////////////////////////////////////
+
+    // Command 1 - Get Battery Level
+    // Request a response packet with Command 1 and matching ID containing the battery voltage and percent charged.
+    pub async fn get_battery_level(&self) -> Result<BatteryLevelResponse, RootError> {
+        let id = self
+            .send_command(RootDeviceId::Battery, 0x01, &[], WriteType::WithResponse)
+            .await?;
+        BatteryLevelResponse::new(
+            self.wait_for_message(RootDeviceId::Battery, 0x01, id)
+                .await?,
+        )
+    }
+
+    /////////////////////////////////////////
+    // Device 16 - Accelerometer
+    /////////////////////////////////////////
+
+    // Command 1 - Get Accelerometer
+    // Request a response packet with Command 1 and matching ID containing the acceleration along each axis in milli-g.
+    pub async fn get_accelerometer(&self) -> Result<AccelerometerResponse, RootError> {
+        let id = self
+            .send_command(
+                RootDeviceId::Accelerometer,
+                0x01,
+                &[],
+                WriteType::WithResponse,
+            )
+            .await?;
+        AccelerometerResponse::new(
+            self.wait_for_message(RootDeviceId::Accelerometer, 0x01, id)
+                .await?,
+        )
+    }
+
+    // Command 2 - Enable Accelerometer
+    // Turn on the accelerometer.
+    pub async fn enable_accelerometer(&self) -> Result<(), RootError> {
+        self.send_command(
+            RootDeviceId::Accelerometer,
+            0x02,
+            &[],
+            WriteType::WithoutResponse,
+        )
+        .await?;
+        Ok(())
+    }
+
+    // Command 3 - Disable Accelerometer
+    // Turn off the accelerometer.
+    pub async fn disable_accelerometer(&self) -> Result<(), RootError> {
+        self.send_command(
+            RootDeviceId::Accelerometer,
+            0x03,
+            &[],
+            WriteType::WithoutResponse,
+        )
+        .await?;
+        Ok(())
+    }
+
+    // Print out chracteristics from the robot
+    pub fn print_characteristics(&self) {
+        self.transport.print_characteristics();
     }
 
     // Notify the tx characteristic that it should send us responses
-    pub async fn subscribe(&self) {
-        let characteristics = self.peripheral.characteristics();
-        let tx_characteristic = characteristics
-            .iter()
-            .find(|c| {
-                c.uuid == ROOT_TX_CHARACTERISTIC && c.properties.contains(CharPropFlags::INDICATE)
-            })
-            .expect("Unable to find read characteristic");
-        self.peripheral
-            .subscribe(tx_characteristic)
-            .await
-            .expect("Failed to subscribe");
+    pub async fn subscribe(&self) -> Result<(), RootError> {
+        Ok(self.transport.subscribe().await?)
     }
 
-    // Process messages in a loop
-    // TODO: Cancle
-    pub async fn run_message_loop(&self) {
+    // Process messages in a loop until stopped through a shutdown handle or the robot is disconnected.
+    // If the link drops it is brought back according to the reconnect policy, giving up with Disconnected
+    // if it can't be.
+    pub async fn run_message_loop(&self) -> Result<(), RootError> {
+        // Keep a single stream open for each connection, re-creating it per message drops anything
+        // that arrives in between
+        let mut notification_stream = self.transport.notifications().await?;
+        let mut connection = self.connection.subscribe();
+        let mut session = connection.borrow_and_update().session;
+        let mut link_check = tokio::time::interval(CONNECTION_CHECK_INTERVAL);
+        let mut shutdown = self.shutdown.subscribe();
+
         loop {
-            let mut notification_stream = self
-                .peripheral
-                .notifications()
-                .await
-                .expect("Message")
-                .take(1);
-
-            while let Some(data) = notification_stream.next().await {
-                let msk = RootMessageKey {
-                    device: data.value[0],
-                    command: data.value[1],
-                    id: data.value[2],
-                };
-
-                //println!("Got message {}", data.value[0]);
-
-                // Sometimes we want to immediately react to a message
-                // TODO: this blocks reading new messages until its completed
-                if msk.device == RootDeviceId::CliffSensor as u8 {
-                    println!("Got cliff sensor message {}", data.value[7]);
-                    if data.value[7] > 0 {
-                        self.stop_and_reset().await;
-                        panic!("Shutting down due to cliff")
+            let data = tokio::select! {
+                // Only whether it happened matters, holding on to the value would keep the loop from being Send
+                _ = async { shutdown.wait_for(|shutdown| *shutdown).await.is_ok() } => return Ok(()),
+                data = notification_stream.next() => data,
+                _ = link_check.tick() => {
+                    if self.transport.is_connected().await? {
+                        continue;
+                    }
+                    None
+                }
+                Ok(()) = connection.changed() => {
+                    // Somebody else reconnected, so listen on the new connection
+                    let current = connection.borrow_and_update().session;
+                    if current != session {
+                        session = current;
+                        notification_stream = self.reopen_notifications().await?;
                     }
-                } else {
-                    self.message_storage
-                        .put_message(msk, Message { data: data.value });
+                    continue;
                 }
+            };
+
+            let Some(data) = data else {
+                if self.connection.borrow().link == LinkState::Closed || *self.shutdown.borrow() {
+                    return Ok(());
+                }
+                if self.transport.is_connected().await? {
+                    // The stream ended without the link going anywhere, so just listen again
+                    notification_stream = self.transport.notifications().await?;
+                    continue;
+                }
+
+                eprintln!("Lost connection to robot, reconnecting...");
+                self.connection
+                    .send_modify(|connection| connection.link = LinkState::Down);
+                if let Err(err) = self.reconnect().await {
+                    // Being disconnected on purpose while reconnecting is a normal way to stop
+                    if self.connection.borrow().link == LinkState::Closed {
+                        return Ok(());
+                    }
+                    return Err(err);
+                }
+                session = connection.borrow_and_update().session;
+                notification_stream = self.reopen_notifications().await?;
+                continue;
+            };
+
+            // Drop anything which got mangled on the way, better to time out than act on garbage
+            if let Err(err) = verify_checked_packet(&data) {
+                self.corrupt_packets.fetch_add(1, Ordering::Relaxed);
+                eprintln!("Dropping corrupt packet {:?}: {}", data, err);
+                continue;
             }
+
+            let msk = RootMessageKey {
+                device: data[0],
+                command: data[1],
+                id: data[2],
+            };
+
+            let event = match RootEvent::decode(&data) {
+                Some(Ok(event)) => event,
+                Some(Err(err)) => {
+                    eprintln!("Dropping malformed event {:?}: {}", data, err);
+                    continue;
+                }
+                None => {
+                    self.message_storage.put_message(msk, Message { data });
+                    continue;
+                }
+            };
+
+            // Sometimes we want to immediately react to a message. Reactions only send commands without
+            // waiting on any response, so reading carries on straight after. A failed reaction is no reason
+            // to stop listening to the robot.
+            if let Err(err) = self.apply_safety_policy(&event).await {
+                eprintln!("Failed to react to {:?}: {}", event, err);
+            }
+
+            // Nobody subscribed is fine, the event is just dropped
+            let _ = self.events.send(event);
         }
     }
 
-    // Disconnects from the peripheral
-    pub async fn disconnect(&self) {
-        if self
-            .peripheral
-            .is_connected()
+    // Stream of every event the robot sends from now on, while the message loop is running.
+    // Events which arrive faster than they are read are dropped, oldest first.
+    pub fn events(&self) -> impl Stream<Item = RootEvent> {
+        BroadcastStream::new(self.events.subscribe()).filter_map(|event| async move { event.ok() })
+    }
+
+    // Number of packets dropped by the message loop for failing validation
+    pub fn corrupt_packet_count(&self) -> usize {
+        self.corrupt_packets.load(Ordering::Relaxed)
+    }
+
+    // React to an event however the safety policy says to
+    async fn apply_safety_policy(&self, event: &RootEvent) -> Result<(), RootError> {
+        let reason = match event {
+            RootEvent::Cliff(_) => "Cliff detected",
+            RootEvent::Bumper(_) => "Bumper pressed",
+            RootEvent::MotorStall(_) => "Motor stalled",
+            RootEvent::TouchSensor(_) => "Touch sensor pressed",
+            RootEvent::Light(_) => "Light changed",
+            RootEvent::ColorSensor(_) => "Color changed",
+            RootEvent::BatteryLevel(_) => "Battery low",
+        };
+
+        match self.safety_policy.action(event) {
+            SafetyAction::Ignore => {
+                // The sensor which caused a back off is saying things are fine again
+                let mut resume_on_clear = self.resume_on_clear.lock().unwrap();
+                if *resume_on_clear == Some(discriminant(event)) {
+                    *resume_on_clear = None;
+                    self.safety_stop.send_replace(None);
+                }
+            }
+            SafetyAction::Stop => {
+                self.stop_and_reset().await?;
+                self.trigger_safety_stop(reason);
+            }
+            SafetyAction::PauseAndAsk => {
+                self.stop_and_reset().await?;
+                self.pause_for_safety(reason);
+            }
+            SafetyAction::BackOff(distance_mm) => {
+                self.stop_and_reset().await?;
+
+                // Already stopped means there is nothing to back away from, and nothing to resume
+                if self.check_safety_stop().is_ok() {
+                    // Not waited on, the message loop would be waiting for itself
+                    self.send_command(
+                        RootDeviceId::Motors,
+                        0x08,
+                        &(-(distance_mm as i32)).to_be_bytes(),
+                        WriteType::WithoutResponse,
+                    )
+                    .await?;
+                    self.pause_for_safety(reason);
+                    *self.resume_on_clear.lock().unwrap() = Some(discriminant(event));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Fail any pending and future commands until the safety stop is cleared.
+    // Anything in progress is abandoned rather than waiting to carry on.
+    pub fn trigger_safety_stop(&self, reason: &str) {
+        println!("Safety stop: {}", reason);
+        *self.resume_on_clear.lock().unwrap() = None;
+        self.safety_stop.send_replace(Some(SafetyStop {
+            reason: reason.to_string(),
+            resumable: false,
+        }));
+    }
+
+    // Fail any pending and future commands until the safety stop is cleared, after which anything
+    // waiting in wait_for_resume carries on. Does nothing if the robot is already stopped for good.
+    pub fn pause_for_safety(&self, reason: &str) {
+        println!("Safety pause: {}", reason);
+        self.safety_stop
+            .send_if_modified(|safety_stop| match safety_stop {
+                Some(stop) if !stop.resumable => false,
+                _ => {
+                    *safety_stop = Some(SafetyStop {
+                        reason: reason.to_string(),
+                        resumable: true,
+                    });
+                    true
+                }
+            });
+    }
+
+    // Allow commands to be sent again after a safety stop
+    pub fn clear_safety_stop(&self) {
+        *self.resume_on_clear.lock().unwrap() = None;
+        self.safety_stop.send_replace(None);
+    }
+
+    // Wait until a safety pause is cleared. Fails straight away if the robot was stopped for good.
+    pub async fn wait_for_resume(&self) -> Result<(), RootError> {
+        let mut safety_stop = self.safety_stop.subscribe();
+        let safety_stop = safety_stop
+            .wait_for(|safety_stop| safety_stop.as_ref().is_none_or(|stop| !stop.resumable))
             .await
-            .expect("Peripheral is not connected")
-        {
-            println!(
-                "Disconnecting from peripheral {:?}...",
-                self.peripheral.id()
-            );
-            self.peripheral
-                .disconnect()
-                .await
-                .expect("Error disconnecting from BLE peripheral");
+            .map_err(|_| RootError::Disconnected)?;
+
+        match safety_stop.as_ref() {
+            Some(stop) => Err(RootError::SafetyStop(stop.reason.clone())),
+            None => Ok(()),
+        }
+    }
+
+    fn check_safety_stop(&self) -> Result<(), RootError> {
+        match self.safety_stop.borrow().as_ref() {
+            Some(stop) => Err(RootError::SafetyStop(stop.reason.clone())),
+            None => Ok(()),
+        }
+    }
+
+    // Handle for stopping the message loop from elsewhere, without needing the robot itself
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            shutdown: self.shutdown.clone(),
+        }
+    }
+
+    // Leave the robot safe before letting go of it: stop any motion, lift the marker, then stop the message
+    // loop and disconnect. Every step is tried even if one before it failed, giving back the first failure.
+    pub async fn shutdown(&self) -> Result<(), RootError> {
+        let stopped = self.stop_and_reset().await;
+        // Lifting the marker waits for its response, so the message loop has to keep going until it is up
+        let lifted = self.set_marker_position(MarkerPosition::NothingDown).await;
+        self.shutdown_handle().shutdown();
+        let disconnected = self.disconnect().await;
+        stopped.and(lifted).and(disconnected)
+    }
+
+    // Disconnects from the peripheral
+    pub async fn disconnect(&self) -> Result<(), RootError> {
+        self.connection
+            .send_modify(|connection| connection.link = LinkState::Closed);
+        if self.transport.is_connected().await? {
+            self.transport.disconnect().await?;
+        }
+        Ok(())
+    }
+
+    // Number of times the link has been brought back after dropping. The robot resets its position on
+    // every new connection, so anything tracking where the robot is needs to start again when this changes.
+    pub fn session(&self) -> u32 {
+        self.connection.borrow().session
+    }
+
+    // Bring the link back if it has gone, trying as often as the reconnect policy allows and subscribing
+    // to the robot again once connected. Does nothing if the link is already up, and fails with Disconnected
+    // without trying if it was closed on purpose, even part way through.
+    pub async fn reconnect(&self) -> Result<(), RootError> {
+        let _reconnecting = self.reconnecting.lock().await;
+        if self.connection.borrow().link == LinkState::Up && self.transport.is_connected().await? {
+            return Ok(());
+        }
+        let mut closed = false;
+        self.connection.send_if_modified(|connection| {
+            closed = connection.link == LinkState::Closed;
+            if !closed {
+                connection.link = LinkState::Down;
+            }
+            !closed
+        });
+        if closed {
+            return Err(RootError::Disconnected);
+        }
+
+        let policy = self.reconnect_policy;
+        let mut backoff = policy.initial_backoff;
+        for attempt in 1..=policy.attempts {
+            if self.connection.borrow().link == LinkState::Closed {
+                return Err(RootError::Disconnected);
+            }
+            let result = match self.transport.connect().await {
+                Ok(()) => self.transport.subscribe().await,
+                Err(err) => Err(err),
+            };
+            let result = match result {
+                Ok(()) => self.transport.notifications().await,
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(notifications) => {
+                    *self.reconnected_notifications.lock().unwrap() = Some(notifications);
+                    self.connection.send_modify(|connection| {
+                        connection.session += 1;
+                        connection.link = LinkState::Up;
+                    });
+                    println!("Reconnected after {} attempts", attempt);
+                    return Ok(());
+                }
+                Err(err) => eprintln!("Reconnect attempt {} failed: {}", attempt, err),
+            }
+
+            if attempt < policy.attempts {
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(policy.max_backoff);
+            }
+        }
+        Err(RootError::Disconnected)
+    }
+
+    // Notifications for the connection reconnect just made, opening them fresh if there are none waiting
+    async fn reopen_notifications(&self) -> Result<NotificationStream, RootError> {
+        let reconnected = self.reconnected_notifications.lock().unwrap().take();
+        match reconnected {
+            Some(notifications) => Ok(notifications),
+            None => Ok(self.transport.notifications().await?),
         }
     }
 
     // wait for a message to be received by the robot
-    // TODO: Async
-    pub fn wait_for_message(&self, device: RootDeviceId, command: u8, id: u8) -> Message {
+    pub async fn wait_for_message(
+        &self,
+        device: RootDeviceId,
+        command: u8,
+        id: u8,
+    ) -> Result<Message, RootError> {
+        self.wait_for_motion(device, command, id, 0.0).await
+    }
+
+    // wait for a message which is only sent once a motion of roughly motion_secs has finished
+    async fn wait_for_motion(
+        &self,
+        device: RootDeviceId,
+        command: u8,
+        id: u8,
+        motion_secs: f32,
+    ) -> Result<Message, RootError> {
+        self.wait_for_response(device, command, id, motion_secs, true)
+            .await
+    }
+
+    // wait for a response, giving up early on a safety stop only if stop_on_safety_stop is set
+    async fn wait_for_response(
+        &self,
+        device: RootDeviceId,
+        command: u8,
+        id: u8,
+        motion_secs: f32,
+        stop_on_safety_stop: bool,
+    ) -> Result<Message, RootError> {
+        let device = device as u8;
+        let timeout = *self
+            .command_timeouts
+            .get(&(device, command))
+            .unwrap_or(&self.response_timeout);
+
         let msk = RootMessageKey {
-            device: device as u8,
-            command: command,
-            id: id,
+            device,
+            command,
+            id,
         };
 
-        // TODO: Handle no message case
-        self.message_storage.wait_for_message(msk).unwrap()
+        // Give up early if the robot gets stopped for safety or the link drops while we wait, a response
+        // sent before a drop is never coming
+        let mut safety_stop = self.safety_stop.subscribe();
+        let mut connection = self.connection.subscribe();
+        let session = connection.borrow().session;
+        let message = self
+            .message_storage
+            .wait_for_message(msk, timeout + Duration::from_secs_f32(motion_secs));
+        tokio::select! {
+            message = message => Ok(message?),
+            Ok(stop) = safety_stop.wait_for(|stop| stop.is_some()), if stop_on_safety_stop => {
+                Err(RootError::SafetyStop(stop.as_ref().map(|stop| stop.reason.clone()).unwrap_or_default()))
+            }
+            Ok(_) = connection.wait_for(|connection| {
+                connection.session != session || connection.link != LinkState::Up
+            }) => Err(RootError::ConnectionLost),
+        }
+    }
+
+    // Stamp a command with the next packet ID and send it, returning the ID used so the response
+    // can be matched back up with it
+    pub async fn send_command(
+        &self,
+        device: RootDeviceId,
+        command: u8,
+        payload: &[u8],
+        write_type: WriteType,
+    ) -> Result<u8, RootError> {
+        let device = device as u8;
+        let id = self.next_packet_id.fetch_add(1, Ordering::Relaxed);
+
+        // Forget any unclaimed response from the last time this ID came around
+        self.message_storage.remove_message(&RootMessageKey {
+            device,
+            command,
+            id,
+        });
+
+        let mut packet = vec![device, command, id];
+        packet.extend_from_slice(payload);
+        self.send_msg(packet, write_type).await?;
+        Ok(id)
     }
 
     // Calculate the CRC and send the message to the robot
-    pub async fn send_msg(&self, vector: Vec<u8>, write_type: WriteType) {
-        let characteristics = self.peripheral.characteristics();
-
-        // TODO: Do I need to find this each time?
-        let rx_characteristic = characteristics
-            .iter()
-            .find(|c| c.uuid == ROOT_RX_CHARACTERISTIC)
-            .expect("Unable to find write characteristic");
-
-        if self
-            .peripheral
-            .is_connected()
-            .await
-            .expect("Failed to check if device is connected")
-        {
-            self.peripheral
-                .write(rx_characteristic, &build_checked_packet(vector), write_type)
-                .await
-                .expect("Failed to send message");
+    pub async fn send_msg(&self, vector: Vec<u8>, write_type: WriteType) -> Result<(), RootError> {
+        if !allowed_during_safety_stop(&vector) {
+            self.check_safety_stop()?;
+        }
+
+        if !self.transport.is_connected().await? {
+            return Err(RootError::Disconnected);
         }
+
+        self.transport
+            .write_packet(&build_checked_packet(vector), write_type)
+            .await?;
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::irobot::root::messages::{BumperEvent, Color, TouchSensorEvent};
+    use crate::irobot::root::spawn_message_loop;
+    use crate::irobot::root::transport::LoopbackTransport;
+    use std::sync::Arc;
 
     #[test]
     fn test_drive_forward_crc() {
@@ -417,4 +1482,430 @@ mod tests {
         ];
         assert_eq!(build_checked_packet(test_vector)[19], 0x71);
     }
+
+    #[tokio::test]
+    async fn can_send_packet_over_transport() {
+        let (transport, mut peer) = LoopbackTransport::pair();
+        let robot = RootRobot::new(transport);
+
+        robot.stop_and_reset().await.unwrap();
+
+        let packet = peer.next_packet().await.unwrap();
+        assert_eq!(packet, build_checked_packet(vec![0x00, 0x03]));
+    }
+
+    #[tokio::test]
+    async fn fails_send_when_disconnected() {
+        let (transport, mut peer) = LoopbackTransport::pair();
+        let robot = RootRobot::new(transport);
+
+        robot.disconnect().await.unwrap();
+        let result = robot.stop_and_reset().await;
+        drop(robot);
+
+        assert!(matches!(result, Err(RootError::Disconnected)));
+        assert_eq!(peer.next_packet().await, None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn can_wait_for_response_over_transport() {
+        let (transport, mut peer) = LoopbackTransport::pair();
+        let robot = spawn_message_loop(RootRobot::new(transport));
+
+        tokio::spawn(async move {
+            let packet = peer.next_packet().await.unwrap();
+            assert_eq!(&packet[0..2], &[0x02, 0x00]);
+            peer.notify(build_checked_packet(vec![0x02, 0x00, packet[2], 0x01]));
+        });
+
+        robot
+            .set_marker_position(MarkerPosition::MarkerDown)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn times_out_waiting_for_lost_response() {
+        let (transport, _peer) = LoopbackTransport::pair();
+        let mut robot = RootRobot::new(transport);
+        robot.set_command_timeout(RootDeviceId::Marker, 0x00, Duration::from_millis(10));
+
+        let result = robot
+            .wait_for_message(RootDeviceId::Marker, 0x00, 0x13)
+            .await;
+
+        assert!(matches!(result, Err(RootError::TimedOut(_))));
+    }
+
+    #[tokio::test]
+    async fn safety_stop_fails_pending_and_new_commands() {
+        let (transport, mut peer) = LoopbackTransport::pair();
+        let robot = Arc::new(RootRobot::new(transport));
+
+        let stop_robot = robot.clone();
+        let stopper = tokio::spawn(async move {
+            peer.next_packet().await.unwrap();
+            stop_robot.trigger_safety_stop("Testing");
+            peer
+        });
+
+        let result = robot.drive_distance(100).await;
+        assert!(matches!(result, Err(RootError::SafetyStop(_))));
+        let _peer = stopper.await.unwrap();
+
+        let result = robot.drive_distance(100).await;
+        assert!(matches!(result, Err(RootError::SafetyStop(_))));
+
+        // Stopping is still allowed, and commands can be sent again once cleared
+        robot.stop_and_reset().await.unwrap();
+        robot.clear_safety_stop();
+        robot.reset_position().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn keeps_running_when_safety_reaction_fails() {
+        let (transport, peer) = LoopbackTransport::pair();
+        let mut robot = RootRobot::new(transport);
+        robot.set_safety_policy(DefaultSafetyPolicy {
+            bumper: SafetyAction::Stop,
+            ..Default::default()
+        });
+        let robot = Arc::new(robot);
+        let mut events = Box::pin(robot.events());
+
+        peer.notify(build_checked_packet(vec![
+            0x0C, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0A, 0x80,
+        ]));
+        peer.notify(build_checked_packet(vec![
+            0x11, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0B, 0x80,
+        ]));
+        // Nothing is reading writes any more, so stopping for the bumper fails
+        drop(peer);
+
+        let loop_robot = robot.clone();
+        let message_loop = tokio::spawn(async move { loop_robot.run_message_loop().await });
+
+        assert!(matches!(events.next().await, Some(RootEvent::Bumper(_))));
+        assert!(matches!(
+            events.next().await,
+            Some(RootEvent::TouchSensor(_))
+        ));
+        assert!(!message_loop.is_finished());
+        message_loop.abort();
+    }
+
+    #[tokio::test]
+    async fn back_off_pauses_until_condition_clears() {
+        let (transport, mut peer) = LoopbackTransport::pair();
+        let mut robot = RootRobot::new(transport);
+        robot.set_safety_policy(DefaultSafetyPolicy {
+            bumper: SafetyAction::BackOff(30),
+            ..Default::default()
+        });
+
+        peer.notify(build_checked_packet(vec![
+            0x0C, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0A, 0x80,
+        ]));
+
+        let robot = spawn_message_loop(robot);
+
+        assert_eq!(&peer.next_packet().await.unwrap()[0..2], &[0x00, 0x03]);
+        let back_off = peer.next_packet().await.unwrap();
+        assert_eq!(&back_off[0..2], &[0x01, 0x08]);
+        assert_eq!(i32::from_be_bytes(back_off[3..7].try_into().unwrap()), -30);
+
+        let result = robot.drive_distance(100).await;
+        assert!(matches!(result, Err(RootError::SafetyStop(_))));
+
+        // Releasing the bumper lets the robot carry on
+        peer.notify(build_checked_packet(vec![
+            0x0C, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0B, 0x00,
+        ]));
+        robot.wait_for_resume().await.unwrap();
+        robot.reset_position().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn stop_is_not_resumable() {
+        let (transport, _peer) = LoopbackTransport::pair();
+        let robot = RootRobot::new(transport);
+
+        robot.pause_for_safety("Pausing");
+        robot.trigger_safety_stop("Stopping");
+        robot.pause_for_safety("Pausing again");
+
+        let result = robot.wait_for_resume().await;
+        assert!(matches!(result, Err(RootError::SafetyStop(reason)) if reason == "Stopping"));
+    }
+
+    #[test]
+    fn can_verify_checked_packet() {
+        let packet = build_checked_packet(vec![0x01, 0x08, 0x11]);
+        assert!(verify_checked_packet(&packet).is_ok());
+
+        let mut corrupt = packet.clone();
+        corrupt[5] ^= 0x01;
+        assert!(matches!(
+            verify_checked_packet(&corrupt),
+            Err(RootError::CrcMismatch)
+        ));
+
+        assert!(matches!(
+            verify_checked_packet(&packet[0..8]),
+            Err(RootError::MalformedResponse(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn shutdown_handle_stops_message_loop() {
+        let (transport, peer) = LoopbackTransport::pair();
+        let robot = Arc::new(RootRobot::new(transport));
+        let handle = robot.shutdown_handle();
+
+        let loop_robot = robot.clone();
+        let message_loop = tokio::spawn(async move { loop_robot.run_message_loop().await });
+
+        assert!(!handle.is_shutdown());
+        handle.clone().shutdown();
+        assert!(handle.is_shutdown());
+        let result = tokio::time::timeout(Duration::from_secs(1), message_loop)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(result.is_ok());
+        // Stopping the loop alone leaves the link alone
+        assert!(peer.is_connected());
+
+        // A loop started after shutting down stops straight away
+        robot.run_message_loop().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn shuts_down_by_stopping_lifting_marker_and_disconnecting() {
+        let (transport, mut peer) = LoopbackTransport::pair();
+        let robot = Arc::new(RootRobot::new(transport));
+
+        let loop_robot = robot.clone();
+        let message_loop = tokio::spawn(async move { loop_robot.run_message_loop().await });
+
+        let peer = tokio::spawn(async move {
+            let stop = peer.next_packet().await.unwrap();
+            assert_eq!(&stop[0..2], &[0x00, 0x03]);
+            let marker = peer.next_packet().await.unwrap();
+            assert_eq!(&marker[0..2], &[0x02, 0x00]);
+            assert_eq!(marker[3], MarkerPosition::NothingDown as u8);
+            peer.notify(build_checked_packet(vec![0x02, 0x00, marker[2], 0x00]));
+            peer
+        });
+
+        robot.shutdown().await.unwrap();
+        let peer = peer.await.unwrap();
+        assert!(!peer.is_connected());
+        let result = tokio::time::timeout(Duration::from_secs(1), message_loop)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn shutdown_lifts_marker_after_safety_stop() {
+        let (transport, mut peer) = LoopbackTransport::pair();
+        let robot = Arc::new(RootRobot::new(transport));
+
+        let loop_robot = robot.clone();
+        let message_loop = tokio::spawn(async move { loop_robot.run_message_loop().await });
+
+        robot.trigger_safety_stop("Testing");
+        let peer = tokio::spawn(async move {
+            peer.next_packet().await.unwrap();
+            let marker = peer.next_packet().await.unwrap();
+            assert_eq!(&marker[0..2], &[0x02, 0x00]);
+            peer.notify(build_checked_packet(vec![0x02, 0x00, marker[2], 0x00]));
+            peer
+        });
+
+        robot.shutdown().await.unwrap();
+        peer.await.unwrap();
+        message_loop.await.unwrap().unwrap();
+
+        // Putting the marker down is still refused
+        let result = robot.set_marker_position(MarkerPosition::MarkerDown).await;
+        assert!(matches!(result, Err(RootError::SafetyStop(_))));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn stays_disconnected_after_asking_robot_to_disconnect() {
+        let (transport, mut peer) = LoopbackTransport::pair();
+        let robot = Arc::new(RootRobot::new(transport));
+
+        let loop_robot = robot.clone();
+        let message_loop = tokio::spawn(async move { loop_robot.run_message_loop().await });
+
+        robot.request_disconnect().await.unwrap();
+        assert_eq!(&peer.next_packet().await.unwrap()[0..2], &[0x00, 0x06]);
+        peer.drop_connection();
+
+        let result = tokio::time::timeout(Duration::from_secs(1), message_loop)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(result.is_ok());
+        assert_eq!(peer.connections(), 0);
+        assert!(matches!(
+            robot.reconnect().await,
+            Err(RootError::Disconnected)
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn fails_waits_and_reconnects_when_link_drops() {
+        let (transport, mut peer) = LoopbackTransport::pair();
+        let robot = spawn_message_loop(RootRobot::new(transport));
+
+        // Drop the link instead of answering, then answer once reconnected
+        tokio::spawn(async move {
+            peer.next_packet().await.unwrap();
+            peer.drop_connection();
+            let packet = peer.next_packet().await.unwrap();
+            peer.notify(build_checked_packet(vec![0x02, 0x00, packet[2], 0x00]));
+            peer
+        });
+
+        let started = std::time::Instant::now();
+        let result = robot.set_marker_position(MarkerPosition::NothingDown).await;
+        assert!(matches!(result, Err(RootError::ConnectionLost)));
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        robot.reconnect().await.unwrap();
+        assert_eq!(robot.session(), 1);
+        robot
+            .set_marker_position(MarkerPosition::NothingDown)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn splits_long_phrases_between_words() {
+        assert_eq!(split_phrase("Hello"), vec!["Hello"]);
+        assert!(split_phrase("").is_empty());
+        assert_eq!(
+            split_phrase("Hello there, how are you today?"),
+            vec!["Hello there, ", "how are you ", "today?"]
+        );
+        assert_eq!(
+            split_phrase("Supercalifragilistic"),
+            vec!["Supercalifragili", "stic"]
+        );
+        // Each é is two bytes, so the 16th byte falls in the middle of one
+        assert_eq!(split_phrase("aéééééééé"), vec!["aééééééé", "é"]);
+    }
+
+    #[tokio::test]
+    async fn drops_corrupt_packets() {
+        let (transport, peer) = LoopbackTransport::pair();
+        let robot = RootRobot::new(transport);
+
+        let mut corrupt = build_checked_packet(vec![0x02, 0x00, 0x13, 0x01]);
+        corrupt[19] ^= 0xFF;
+        peer.notify(corrupt);
+        peer.notify(vec![0x02, 0x00]);
+        peer.notify(build_checked_packet(vec![0x02, 0x00, 0x13, 0x01]));
+
+        let robot = spawn_message_loop(robot);
+
+        let message = robot
+            .wait_for_message(RootDeviceId::Marker, 0x00, 0x13)
+            .await
+            .unwrap();
+        assert_eq!(message.data[3], 0x01);
+        assert_eq!(robot.corrupt_packet_count(), 2);
+        drop(peer);
+    }
+
+    #[tokio::test]
+    async fn matches_pipelined_responses_by_id() {
+        let (transport, mut peer) = LoopbackTransport::pair();
+        let robot = spawn_message_loop(RootRobot::new(transport));
+
+        // Answer both marker commands once they have both been sent, in reverse order
+        tokio::spawn(async move {
+            let first = peer.next_packet().await.unwrap();
+            let second = peer.next_packet().await.unwrap();
+            assert_ne!(first[2], second[2]);
+
+            peer.notify(build_checked_packet(vec![0x02, 0x00, second[2], second[3]]));
+            peer.notify(build_checked_packet(vec![0x02, 0x00, first[2], first[3]]));
+            peer
+        });
+
+        let (down, up) = tokio::join!(
+            robot.set_marker_position(MarkerPosition::MarkerDown),
+            robot.set_marker_position(MarkerPosition::NothingDown)
+        );
+
+        assert_eq!(down.unwrap().position, MarkerPosition::MarkerDown as u8);
+        assert_eq!(up.unwrap().position, MarkerPosition::NothingDown as u8);
+    }
+
+    #[tokio::test]
+    async fn streams_color_sensor_events() {
+        let (transport, peer) = LoopbackTransport::pair();
+        let robot = RootRobot::new(transport);
+        let mut events = Box::pin(robot.color_sensor_events());
+
+        let mut line = vec![0x04, 0x02, 0x00];
+        line.extend_from_slice(&[0x00; 7]);
+        line.extend_from_slice(&[0x11; 2]);
+        line.extend_from_slice(&[0x00; 7]);
+        peer.notify(build_checked_packet(line));
+
+        spawn_message_loop(robot);
+
+        let event = events.next().await.unwrap();
+        assert_eq!(event.colors[13], Color::White);
+        assert_eq!(event.colors[14..18], [Color::Black; 4]);
+        assert_eq!(event.colors[18], Color::White);
+        drop(peer);
+    }
+
+    #[tokio::test]
+    async fn streams_events_and_stores_responses() {
+        let (transport, peer) = LoopbackTransport::pair();
+        let robot = RootRobot::new(transport);
+        let mut events = Box::pin(robot.events());
+
+        peer.notify(build_checked_packet(vec![
+            0x0C, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0A, 0x40,
+        ]));
+        peer.notify(build_checked_packet(vec![0x02, 0x00, 0x13, 0x01]));
+        peer.notify(build_checked_packet(vec![
+            0x11, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0B, 0x80,
+        ]));
+
+        let robot = spawn_message_loop(robot);
+
+        assert!(matches!(
+            events.next().await,
+            Some(RootEvent::Bumper(BumperEvent {
+                left: false,
+                right: true,
+                ..
+            }))
+        ));
+        assert!(matches!(
+            events.next().await,
+            Some(RootEvent::TouchSensor(TouchSensorEvent {
+                front_left: true,
+                ..
+            }))
+        ));
+
+        let message = robot
+            .wait_for_message(RootDeviceId::Marker, 0x00, 0x13)
+            .await
+            .unwrap();
+        assert_eq!(message.data[3], 0x01);
+        drop(peer);
+    }
 }
@@ -0,0 +1,113 @@
+use super::RootEvent;
+
+// What the robot should do when it reports something that might need it to stop
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SafetyAction {
+    // Carry on as if nothing happened
+    Ignore,
+    // Stop and refuse every command until clear_safety_stop, anything in progress is abandoned
+    Stop,
+    // Stop, reverse this many mm, then carry on by itself once the robot reports the condition has cleared
+    BackOff(u16),
+    // Stop until clear_safety_stop, after which anything in progress can pick up where it left off
+    PauseAndAsk,
+}
+
+// Decides how RootRobot reacts to each event it receives. Any Fn(&RootEvent) -> SafetyAction will do.
+pub trait SafetyPolicy: Send + Sync {
+    fn action(&self, event: &RootEvent) -> SafetyAction;
+}
+
+impl<F: Fn(&RootEvent) -> SafetyAction + Send + Sync> SafetyPolicy for F {
+    fn action(&self, event: &RootEvent) -> SafetyAction {
+        self(event)
+    }
+}
+
+// Takes the same action every time a given sensor reports trouble
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DefaultSafetyPolicy {
+    pub cliff: SafetyAction,
+    pub bumper: SafetyAction,
+    pub motor_stall: SafetyAction,
+    pub low_battery: SafetyAction,
+    // Battery events at or below this are treated as low
+    pub low_battery_percent: u8,
+}
+
+impl Default for DefaultSafetyPolicy {
+    fn default() -> Self {
+        DefaultSafetyPolicy {
+            cliff: SafetyAction::Stop,
+            bumper: SafetyAction::Ignore,
+            motor_stall: SafetyAction::Ignore,
+            low_battery: SafetyAction::Ignore,
+            low_battery_percent: 10,
+        }
+    }
+}
+
+impl SafetyPolicy for DefaultSafetyPolicy {
+    fn action(&self, event: &RootEvent) -> SafetyAction {
+        match event {
+            RootEvent::Cliff(cliff) if cliff.cliff => self.cliff,
+            RootEvent::Bumper(bumper) if bumper.left || bumper.right => self.bumper,
+            RootEvent::MotorStall(_) => self.motor_stall,
+            RootEvent::BatteryLevel(battery) if battery.percent <= self.low_battery_percent => {
+                self.low_battery
+            }
+            _ => SafetyAction::Ignore,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irobot::root::messages::{BatteryLevelEvent, BumperEvent, CliffEvent};
+
+    #[test]
+    fn only_acts_on_trouble() {
+        let policy = DefaultSafetyPolicy {
+            bumper: SafetyAction::BackOff(30),
+            ..Default::default()
+        };
+        let cliff = |cliff| {
+            RootEvent::Cliff(CliffEvent {
+                timestamp: 0,
+                cliff,
+                sensor: 0,
+                threshold: 0,
+            })
+        };
+        let bumper = |left, right| {
+            RootEvent::Bumper(BumperEvent {
+                timestamp: 0,
+                left,
+                right,
+            })
+        };
+
+        assert_eq!(policy.action(&cliff(true)), SafetyAction::Stop);
+        assert_eq!(policy.action(&cliff(false)), SafetyAction::Ignore);
+        assert_eq!(
+            policy.action(&bumper(false, true)),
+            SafetyAction::BackOff(30)
+        );
+        assert_eq!(policy.action(&bumper(false, false)), SafetyAction::Ignore);
+
+        let policy = DefaultSafetyPolicy {
+            low_battery: SafetyAction::PauseAndAsk,
+            ..Default::default()
+        };
+        let battery = |percent| {
+            RootEvent::BatteryLevel(BatteryLevelEvent {
+                timestamp: 0,
+                voltage: 0,
+                percent,
+            })
+        };
+        assert_eq!(policy.action(&battery(10)), SafetyAction::PauseAndAsk);
+        assert_eq!(policy.action(&battery(11)), SafetyAction::Ignore);
+    }
+}
@@ -0,0 +1,814 @@
+use std::sync::{Arc, Mutex};
+
+use super::root_robot::{build_checked_packet, verify_checked_packet};
+use super::transport::{LoopbackPeer, LoopbackTransport};
+use super::RootRobot;
+use crate::utils::Point;
+
+// Speeds used to estimate how long each motion takes, roughly what a real Root does by default
+const DRIVE_SPEED_MM_PER_SEC: f32 = 100.0;
+const ROTATE_SPEED_DEGREES_PER_SEC: f32 = 90.0;
+const MARKER_MOVE_MS: u32 = 500;
+
+// What a color sensor reads looking at a white board with its light on
+const WHITE_ADC_COUNTS: u16 = 0x0FFF;
+const WHITE_MILLIVOLTS: u16 = 3300;
+
+// Battery voltage reported when full and when flat
+const BATTERY_FULL_MILLIVOLTS: u16 = 4200;
+const BATTERY_EMPTY_MILLIVOLTS: u16 = 3400;
+
+// Arcs are recorded in the trail as short straight pieces of at most this many degrees
+const ARC_STEP_DEGREES: f32 = 5.0;
+
+// Versions reported by the simulator when asked for Get Versions
+const SIMULATED_VERSIONS: [u8; 10] = [0xA5, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x04, 0x00];
+
+// Identity reported by the simulator, padded with zeros the same as a real robot
+const SIMULATED_NAME: &str = "Root";
+const SIMULATED_SERIAL_NUMBER: &str = "RT0000000000";
+const SIMULATED_SKU: &str = "RT0-SIM";
+
+// Where the simulated robot currently thinks it is, using the same frame as the robot.
+// Heading is in degrees where 90 is pointing along positive y.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SimulatedPose {
+    pub x_coord: f32,
+    pub y_coord: f32,
+    pub heading: f32,
+}
+
+// A continuous run of movement, either drawing with the marker down, erasing with the eraser down
+// or travelling with both up
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrailSegment {
+    pub points: Vec<Point>,
+    pub marker_down: bool,
+    pub eraser_down: bool,
+}
+
+struct SimulatedRootState {
+    pose: SimulatedPose,
+    marker_position: u8,
+    timestamp_ms: u32,
+    trail: Vec<TrailSegment>,
+    // Speeds are only recorded, the simulator has no sense of time passing between commands
+    motor_speeds: (i32, i32),
+    gravity_compensation: (u8, u16),
+    battery_percent: u8,
+    // Acceleration in milli-g, lying flat unless a test says otherwise
+    accelerometer: (i16, i16, i16),
+    accelerometer_enabled: bool,
+    // Every note played as frequency in Hz and duration in ms, and every phrase said
+    notes: Vec<(u32, u16)>,
+    phrases: Vec<String>,
+    volume: u8,
+    // Last LED animation state and color set
+    lights: (u8, u8, u8, u8),
+    name: String,
+    // Bitfield of devices with events turned on, the same layout as Enable Events
+    enabled_events: [u8; 16],
+    // Packets to go before the link drops, if it is going to, and whether the packet it drops on is answered
+    drop_connection_after: Option<(usize, bool)>,
+}
+
+// In-process stand in for a Root robot. It reads the same CRC checked packets RootRobot writes,
+// keeps track of pose and marker state, and answers with correctly formed responses.
+// Cloning gives another handle onto the same robot so tests can inspect it while it runs.
+#[derive(Clone)]
+pub struct SimulatedRoot {
+    state: Arc<Mutex<SimulatedRootState>>,
+}
+
+impl SimulatedRoot {
+    pub fn new() -> SimulatedRoot {
+        SimulatedRoot {
+            state: Arc::new(Mutex::new(SimulatedRootState {
+                pose: SimulatedPose {
+                    x_coord: 0.0,
+                    y_coord: 0.0,
+                    heading: 90.0,
+                },
+                marker_position: 0x00,
+                timestamp_ms: 0,
+                trail: vec![],
+                motor_speeds: (0, 0),
+                gravity_compensation: (0x00, 500),
+                battery_percent: 100,
+                accelerometer: (0, 0, 1000),
+                accelerometer_enabled: false,
+                notes: vec![],
+                phrases: vec![],
+                volume: 100,
+                lights: (0x00, 0x00, 0x00, 0x00),
+                name: SIMULATED_NAME.to_string(),
+                enabled_events: [0xFF; 16],
+                drop_connection_after: None,
+            })),
+        }
+    }
+
+    // Create a RootRobot connected to this simulator over a loopback transport.
+    // Must be called from within a tokio runtime as the simulator runs as a task.
+    pub fn connect(&self) -> RootRobot {
+        let (transport, peer) = LoopbackTransport::pair();
+        tokio::spawn(self.clone().run(peer));
+        RootRobot::new(transport)
+    }
+
+    // Connect a RootRobot to this simulator with its message loop already running
+    pub fn connect_running(&self) -> Arc<RootRobot> {
+        spawn_message_loop(self.connect())
+    }
+
+    // Answer packets from the peer until the transport is dropped
+    pub async fn run(self, mut peer: LoopbackPeer) {
+        let mut connections = peer.connections();
+        while let Some(packet) = peer.next_packet().await {
+            // A real Root resets its position whenever a new connection is made
+            if peer.connections() != connections {
+                connections = peer.connections();
+                self.state.lock().unwrap().reset_position();
+            }
+
+            let responses = self.handle_packet(&packet);
+            let dropped = self.take_dropped_connection();
+            if dropped != Some(false) {
+                for response in responses {
+                    peer.notify(response);
+                }
+            }
+            if dropped.is_some() {
+                peer.drop_connection();
+            }
+        }
+    }
+
+    // Answer this many more packets, then drop the link as the next one arrives. Like a real robot losing
+    // the link part way through a command, that one is still carried out but never answered.
+    pub fn drop_connection_after(&self, packets: usize) {
+        self.state.lock().unwrap().drop_connection_after = Some((packets, false));
+    }
+
+    // Answer this many more packets, then drop the link straight after answering the last of them, so
+    // nothing sent after it reaches the robot
+    pub fn drop_connection_after_answering(&self, packets: usize) {
+        self.state.lock().unwrap().drop_connection_after = Some((packets.saturating_sub(1), true));
+    }
+
+    // Whether the link drops on this packet, and if so whether the packet is answered first
+    fn take_dropped_connection(&self) -> Option<bool> {
+        let mut state = self.state.lock().unwrap();
+        match state.drop_connection_after {
+            Some((0, answered)) => {
+                state.drop_connection_after = None;
+                Some(answered)
+            }
+            Some((packets, answered)) => {
+                state.drop_connection_after = Some((packets - 1, answered));
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn pose(&self) -> SimulatedPose {
+        self.state.lock().unwrap().pose
+    }
+
+    pub fn marker_position(&self) -> u8 {
+        self.state.lock().unwrap().marker_position
+    }
+
+    // Every movement made so far, in the order it was made
+    pub fn trail(&self) -> Vec<TrailSegment> {
+        self.state.lock().unwrap().trail.clone()
+    }
+
+    // Last left and right motor speeds set, in mm/s
+    pub fn motor_speeds(&self) -> (i32, i32) {
+        self.state.lock().unwrap().motor_speeds
+    }
+
+    // Last gravity compensation mode and amount set
+    pub fn gravity_compensation(&self) -> (u8, u16) {
+        self.state.lock().unwrap().gravity_compensation
+    }
+
+    // Charge reported when asked for the battery level, the simulator never runs down on its own
+    pub fn set_battery_percent(&self, percent: u8) {
+        self.state.lock().unwrap().battery_percent = percent;
+    }
+
+    // Acceleration reported when asked, in milli-g
+    pub fn set_accelerometer(&self, x: i16, y: i16, z: i16) {
+        self.state.lock().unwrap().accelerometer = (x, y, z);
+    }
+
+    pub fn accelerometer_enabled(&self) -> bool {
+        self.state.lock().unwrap().accelerometer_enabled
+    }
+
+    pub fn name(&self) -> String {
+        self.state.lock().unwrap().name.clone()
+    }
+
+    pub fn enabled_events(&self) -> [u8; 16] {
+        self.state.lock().unwrap().enabled_events
+    }
+
+    // Last LED animation state and red, green and blue set
+    pub fn lights(&self) -> (u8, u8, u8, u8) {
+        self.state.lock().unwrap().lights
+    }
+
+    // Notes played so far as frequency in Hz and duration in ms, in the order they were played
+    pub fn notes(&self) -> Vec<(u32, u16)> {
+        self.state.lock().unwrap().notes.clone()
+    }
+
+    // Each piece of a phrase said so far, as sent in a single packet
+    pub fn phrases(&self) -> Vec<String> {
+        self.state.lock().unwrap().phrases.clone()
+    }
+
+    pub fn volume(&self) -> u8 {
+        self.state.lock().unwrap().volume
+    }
+
+    // Milliseconds of simulated time spent carrying out commands
+    pub fn timestamp(&self) -> u32 {
+        self.state.lock().unwrap().timestamp_ms
+    }
+
+    // Process a single packet, returning any packets the robot would send back
+    pub fn handle_packet(&self, packet: &[u8]) -> Vec<Vec<u8>> {
+        // A real robot silently drops packets which fail the checksum
+        if verify_checked_packet(packet).is_err() {
+            return vec![];
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let (device, command, id) = (packet[0], packet[1], packet[2]);
+        let payload = &packet[3..19];
+
+        match (device, command) {
+            // Get Versions
+            (0x00, 0x00) => vec![response(device, command, id, &SIMULATED_VERSIONS)],
+            // Set Name, padding after the name is left off
+            (0x00, 0x01) => {
+                state.name = String::from_utf8_lossy(payload)
+                    .trim_end_matches('\0')
+                    .to_string();
+                vec![]
+            }
+            // Get Name
+            (0x00, 0x02) => vec![response(device, command, id, state.name.as_bytes())],
+            // Stop and Reset
+            (0x00, 0x03) => {
+                state.reset_position();
+                state.marker_position = 0x00;
+                vec![]
+            }
+            // Stop Project
+            (0x00, 0x04) => vec![],
+            // Enable Events
+            (0x00, 0x07) => {
+                for (enabled, bits) in state.enabled_events.iter_mut().zip(payload) {
+                    *enabled |= bits;
+                }
+                vec![]
+            }
+            // Disable Events
+            (0x00, 0x09) => {
+                for (enabled, bits) in state.enabled_events.iter_mut().zip(payload) {
+                    *enabled &= !bits;
+                }
+                vec![]
+            }
+            // Get Enabled Events
+            (0x00, 0x0B) => vec![response(device, command, id, &state.enabled_events)],
+            // Get Serial Number
+            (0x00, 0x0E) => vec![response(
+                device,
+                command,
+                id,
+                SIMULATED_SERIAL_NUMBER.as_bytes(),
+            )],
+            // Get SKU
+            (0x00, 0x0F) => vec![response(device, command, id, SIMULATED_SKU.as_bytes())],
+            // Set Left and Right Motor Speed
+            (0x01, 0x04) => {
+                state.motor_speeds = (read_i32(payload, 0), read_i32(payload, 4));
+                vec![]
+            }
+            // Set Left Motor Speed
+            (0x01, 0x06) => {
+                state.motor_speeds.0 = read_i32(payload, 0);
+                vec![]
+            }
+            // Set Right Motor Speed
+            (0x01, 0x07) => {
+                state.motor_speeds.1 = read_i32(payload, 0);
+                vec![]
+            }
+            // Drive Distance
+            (0x01, 0x08) => {
+                state.drive_distance(read_i32(payload, 0) as f32);
+                vec![state.motion_response(device, command, id)]
+            }
+            // Rotate Angle
+            (0x01, 0x0C) => {
+                state.rotate_angle(read_i32(payload, 0) as f32 / 10.0);
+                vec![state.motion_response(device, command, id)]
+            }
+            // Set Gravity Compensation
+            (0x01, 0x0D) => {
+                state.gravity_compensation =
+                    (payload[0], u16::from_be_bytes([payload[1], payload[2]]));
+                vec![]
+            }
+            // Reset Position
+            (0x01, 0x0F) => {
+                state.reset_position();
+                vec![]
+            }
+            // Get Position
+            (0x01, 0x10) => vec![state.motion_response(device, command, id)],
+            // Navigate to Position
+            (0x01, 0x11) => {
+                state.navigate_to_position(
+                    read_i32(payload, 0) as f32,
+                    read_i32(payload, 4) as f32,
+                    i16::from_be_bytes([payload[8], payload[9]]),
+                );
+                vec![state.motion_response(device, command, id)]
+            }
+            // Drive Arc
+            (0x01, 0x1B) => {
+                state.drive_arc(
+                    read_i32(payload, 0) as f32 / 10.0,
+                    read_i32(payload, 4) as f32,
+                );
+                vec![state.motion_response(device, command, id)]
+            }
+            // Set Marker/Eraser Position
+            (0x02, 0x00) => {
+                state.marker_position = payload[0];
+                state.timestamp_ms += MARKER_MOVE_MS;
+                vec![response(device, command, id, &[payload[0]])]
+            }
+            // Set LED Animation
+            (0x03, 0x02) => {
+                state.lights = (payload[0], payload[1], payload[2], payload[3]);
+                vec![]
+            }
+            // Get Color Sensor Data, the simulated board is plain white so every lit sensor reads full scale
+            (0x04, 0x01) => {
+                let value: u16 = match (payload[1], payload[2]) {
+                    (0x00, _) => 0,
+                    (_, 0x00) => WHITE_ADC_COUNTS,
+                    _ => WHITE_MILLIVOLTS,
+                };
+                let payload = [value.to_be_bytes(); 8].concat();
+                vec![response(device, command, id, &payload)]
+            }
+            // Play Note
+            (0x05, 0x00) => {
+                let duration = u16::from_be_bytes([payload[4], payload[5]]);
+                state.notes.push((read_i32(payload, 0) as u32, duration));
+                state.timestamp_ms += duration as u32;
+                vec![response(device, command, id, &[])]
+            }
+            // Stop Sound
+            (0x05, 0x01) => vec![],
+            // Say Phrase, padding after the phrase is left off
+            (0x05, 0x04) => {
+                let phrase = String::from_utf8_lossy(payload);
+                state
+                    .phrases
+                    .push(phrase.trim_end_matches('\0').to_string());
+                vec![response(device, command, id, &[])]
+            }
+            // Play Sweep
+            (0x05, 0x05) => {
+                state.timestamp_ms += u16::from_be_bytes([payload[8], payload[9]]) as u32;
+                vec![response(device, command, id, &[])]
+            }
+            // Set Volume
+            (0x05, 0x06) => {
+                state.volume = payload[0];
+                vec![]
+            }
+            // Get Battery Level
+            (0x0E, 0x01) => {
+                let mut payload = state.timestamp_ms.to_be_bytes().to_vec();
+                payload.extend_from_slice(&state.battery_voltage().to_be_bytes());
+                payload.push(state.battery_percent);
+                vec![response(device, command, id, &payload)]
+            }
+            // Get Accelerometer
+            (0x10, 0x01) => {
+                let (x, y, z) = state.accelerometer;
+                let mut payload = state.timestamp_ms.to_be_bytes().to_vec();
+                payload.extend_from_slice(&x.to_be_bytes());
+                payload.extend_from_slice(&y.to_be_bytes());
+                payload.extend_from_slice(&z.to_be_bytes());
+                vec![response(device, command, id, &payload)]
+            }
+            // Enable Accelerometer
+            (0x10, 0x02) => {
+                state.accelerometer_enabled = true;
+                vec![]
+            }
+            // Disable Accelerometer
+            (0x10, 0x03) => {
+                state.accelerometer_enabled = false;
+                vec![]
+            }
+            // Commands the simulator doesn't model are accepted without a response
+            _ => vec![],
+        }
+    }
+}
+
+impl SimulatedRootState {
+    fn reset_position(&mut self) {
+        self.pose = SimulatedPose {
+            x_coord: 0.0,
+            y_coord: 0.0,
+            heading: 90.0,
+        };
+    }
+
+    fn drive_distance(&mut self, distance_mm: f32) {
+        let start = self.position();
+        let heading = self.pose.heading.to_radians();
+        self.pose.x_coord += distance_mm * heading.cos();
+        self.pose.y_coord += distance_mm * heading.sin();
+        self.record_trail(start, vec![self.position()]);
+        self.advance_clock(distance_mm.abs() / DRIVE_SPEED_MM_PER_SEC);
+    }
+
+    // Positive angles rotate clockwise
+    fn rotate_angle(&mut self, angle_degrees: f32) {
+        self.pose.heading = normalize_heading(self.pose.heading - angle_degrees);
+        self.advance_clock(angle_degrees.abs() / ROTATE_SPEED_DEGREES_PER_SEC);
+    }
+
+    // Turn to face the target, drive straight to it, then turn to the heading unless it is -1
+    fn navigate_to_position(&mut self, x_coord: f32, y_coord: f32, heading_deci_degrees: i16) {
+        let delta_x = x_coord - self.pose.x_coord;
+        let delta_y = y_coord - self.pose.y_coord;
+        let distance = delta_x.hypot(delta_y);
+
+        if distance > 0.0 {
+            let target_heading = delta_y.atan2(delta_x).to_degrees();
+            self.rotate_angle(shortest_rotation(self.pose.heading, target_heading));
+            self.drive_distance(distance);
+        }
+
+        if heading_deci_degrees >= 0 {
+            let target_heading = heading_deci_degrees as f32 / 10.0;
+            self.rotate_angle(shortest_rotation(self.pose.heading, target_heading));
+        }
+    }
+
+    // Positive angles are clockwise, positive radius puts the center of the arc to the right
+    fn drive_arc(&mut self, angle_degrees: f32, radius_mm: f32) {
+        let right = (self.pose.heading - 90.0).to_radians();
+        let center_x = self.pose.x_coord + radius_mm * right.cos();
+        let center_y = self.pose.y_coord + radius_mm * right.sin();
+
+        // Swing the robot around the center by the angle, a few degrees at a time for the trail
+        let start = self.position();
+        let steps = (angle_degrees.abs() / ARC_STEP_DEGREES).ceil().max(1.0) as usize;
+        let offset_x = self.pose.x_coord - center_x;
+        let offset_y = self.pose.y_coord - center_y;
+        let points = (1..=steps)
+            .map(|step| {
+                let rotation = -(angle_degrees * step as f32 / steps as f32).to_radians();
+                Point::new(
+                    center_x + offset_x * rotation.cos() - offset_y * rotation.sin(),
+                    center_y + offset_x * rotation.sin() + offset_y * rotation.cos(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let end = points[points.len() - 1];
+        self.pose.x_coord = end.x_coord;
+        self.pose.y_coord = end.y_coord;
+        self.record_trail(start, points);
+        self.pose.heading = normalize_heading(self.pose.heading - angle_degrees);
+
+        let arc_length = angle_degrees.to_radians().abs() * radius_mm.abs();
+        self.advance_clock(arc_length / DRIVE_SPEED_MM_PER_SEC);
+    }
+
+    fn position(&self) -> Point {
+        Point::new(self.pose.x_coord, self.pose.y_coord)
+    }
+
+    // Add a movement to the trail, joining it onto the previous segment when it carries straight on
+    fn record_trail(&mut self, start: Point, points: Vec<Point>) {
+        let marker_down = self.marker_position == 0x01;
+        let eraser_down = self.marker_position == 0x02;
+
+        if let Some(last) = self.trail.last_mut() {
+            if last.marker_down == marker_down
+                && last.eraser_down == eraser_down
+                && last.points.last() == Some(&start)
+            {
+                last.points.extend(points);
+                return;
+            }
+        }
+
+        let mut segment_points = vec![start];
+        segment_points.extend(points);
+        self.trail.push(TrailSegment {
+            points: segment_points,
+            marker_down,
+            eraser_down,
+        });
+    }
+
+    // Voltage falls linearly from a full to a flat battery
+    fn battery_voltage(&self) -> u16 {
+        let range = (BATTERY_FULL_MILLIVOLTS - BATTERY_EMPTY_MILLIVOLTS) as u32;
+        BATTERY_EMPTY_MILLIVOLTS + (range * self.battery_percent as u32 / 100) as u16
+    }
+
+    fn advance_clock(&mut self, seconds: f32) {
+        self.timestamp_ms += (seconds * 1000.0).round() as u32;
+    }
+
+    // Drive Distance, Rotate Angle and Drive Arc all finish with the same timestamp and pose payload
+    fn motion_response(&self, device: u8, command: u8, id: u8) -> Vec<u8> {
+        let mut payload = vec![];
+        payload.extend_from_slice(&self.timestamp_ms.to_be_bytes());
+        payload.extend_from_slice(&(self.pose.x_coord.round() as i32).to_be_bytes());
+        payload.extend_from_slice(&(self.pose.y_coord.round() as i32).to_be_bytes());
+        payload.extend_from_slice(&((self.pose.heading * 10.0).round() as i16).to_be_bytes());
+        response(device, command, id, &payload)
+    }
+}
+
+// Run a robot's message loop in the background, handing the robot back ready to send commands to.
+// The loop stops when the robot is shut down or disconnected.
+pub fn spawn_message_loop(robot: RootRobot) -> Arc<RootRobot> {
+    let robot = Arc::new(robot);
+    let loop_robot = robot.clone();
+    tokio::spawn(async move {
+        if let Err(err) = loop_robot.run_message_loop().await {
+            eprintln!("Message loop stopped: {}", err);
+        }
+    });
+    robot
+}
+
+impl Default for SimulatedRoot {
+    fn default() -> Self {
+        SimulatedRoot::new()
+    }
+}
+
+fn response(device: u8, command: u8, id: u8, payload: &[u8]) -> Vec<u8> {
+    let mut packet = vec![device, command, id];
+    packet.extend_from_slice(payload);
+    build_checked_packet(packet)
+}
+
+fn read_i32(payload: &[u8], offset: usize) -> i32 {
+    i32::from_be_bytes(payload[offset..offset + 4].try_into().unwrap())
+}
+
+// Clockwise rotation needed to turn from one heading to another, going whichever way is shorter
+fn shortest_rotation(from: f32, to: f32) -> f32 {
+    (from - to + 180.0).rem_euclid(360.0) - 180.0
+}
+
+// Keep headings between 0 and 360 like the robot reports them
+fn normalize_heading(heading: f32) -> f32 {
+    let heading = heading.rem_euclid(360.0);
+    if heading >= 359.95 {
+        0.0
+    } else {
+        heading
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irobot::root::{Melody, RootDeviceId, RootError};
+
+    fn command(packet: Vec<u8>) -> Vec<u8> {
+        build_checked_packet(packet)
+    }
+
+    fn assert_pose(pose: SimulatedPose, x: f32, y: f32, heading: f32) {
+        assert!((pose.x_coord - x).abs() < 0.01, "x was {}", pose.x_coord);
+        assert!((pose.y_coord - y).abs() < 0.01, "y was {}", pose.y_coord);
+        assert!(
+            (pose.heading - heading).abs() < 0.01,
+            "heading was {}",
+            pose.heading
+        );
+    }
+
+    #[test]
+    fn can_drive_and_rotate() {
+        let sim = SimulatedRoot::new();
+
+        // Drive 100mm straight up
+        let responses = sim.handle_packet(&command(vec![0x01, 0x08, 0x05, 0x00, 0x00, 0x00, 0x64]));
+        assert_eq!(responses.len(), 1);
+        assert_pose(sim.pose(), 0.0, 100.0, 90.0);
+
+        // Rotate 90 degrees clockwise then drive forward 50mm
+        sim.handle_packet(&command(vec![0x01, 0x0C, 0x06, 0x00, 0x00, 0x03, 0x84]));
+        sim.handle_packet(&command(vec![0x01, 0x08, 0x07, 0x00, 0x00, 0x00, 0x32]));
+        assert_pose(sim.pose(), 50.0, 100.0, 0.0);
+
+        // 1 second driving, 1 second rotating, half a second driving
+        assert_eq!(sim.timestamp(), 2500);
+
+        // Both drives happened with the marker up so they join into one travel segment
+        let trail = sim.trail();
+        assert_eq!(trail.len(), 1);
+        assert!(!trail[0].marker_down);
+        assert_eq!(trail[0].points.len(), 3);
+    }
+
+    #[test]
+    fn can_navigate_to_position() {
+        let sim = SimulatedRoot::new();
+
+        // Go to (100, 100) then face along positive x
+        sim.handle_packet(&command(vec![
+            0x01, 0x11, 0x01, 0x00, 0x00, 0x00, 0x64, 0x00, 0x00, 0x00, 0x64, 0x00, 0x00,
+        ]));
+        assert_pose(sim.pose(), 100.0, 100.0, 0.0);
+
+        // Back to the origin leaving the heading alone
+        sim.handle_packet(&command(vec![
+            0x01, 0x11, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF,
+        ]));
+        assert_pose(sim.pose(), 0.0, 0.0, 225.0);
+    }
+
+    #[test]
+    fn can_drive_arc() {
+        let sim = SimulatedRoot::new();
+
+        // Half circle clockwise around a center 50mm to the right
+        sim.handle_packet(&command(vec![
+            0x01, 0x1B, 0x01, 0x00, 0x00, 0x07, 0x08, 0x00, 0x00, 0x00, 0x32,
+        ]));
+        assert_pose(sim.pose(), 100.0, 0.0, 270.0);
+
+        // 180 degrees in 5 degree steps, plus the starting point
+        assert_eq!(sim.trail()[0].points.len(), 37);
+    }
+
+    #[test]
+    fn responds_with_big_endian_pose() {
+        let sim = SimulatedRoot::new();
+
+        let response = sim
+            .handle_packet(&command(vec![0x01, 0x08, 0x2A, 0xFF, 0xFF, 0xFF, 0x9C]))
+            .remove(0);
+
+        assert_eq!(&response[0..3], &[0x01, 0x08, 0x2A]);
+        assert_eq!(u32::from_be_bytes(response[3..7].try_into().unwrap()), 1000);
+        assert_eq!(i32::from_be_bytes(response[7..11].try_into().unwrap()), 0);
+        assert_eq!(
+            i32::from_be_bytes(response[11..15].try_into().unwrap()),
+            -100
+        );
+        assert_eq!(
+            i16::from_be_bytes(response[15..17].try_into().unwrap()),
+            900
+        );
+        assert!(verify_checked_packet(&response).is_ok());
+    }
+
+    #[test]
+    fn tracks_marker_and_ignores_bad_crc() {
+        let sim = SimulatedRoot::new();
+
+        let responses = sim.handle_packet(&command(vec![0x02, 0x00, 0x01, 0x01]));
+        assert_eq!(responses[0][3], 0x01);
+        assert_eq!(sim.marker_position(), 0x01);
+
+        sim.handle_packet(&command(vec![0x01, 0x08, 0x02, 0x00, 0x00, 0x00, 0x0A]));
+        let trail = sim.trail();
+        assert_eq!(trail.len(), 1);
+        assert!(trail[0].marker_down);
+        assert!((trail[0].points[1].y_coord - 10.0).abs() < 0.01);
+
+        let mut corrupt = command(vec![0x02, 0x00, 0x01, 0x00]);
+        corrupt[19] ^= 0xFF;
+        assert!(sim.handle_packet(&corrupt).is_empty());
+        assert_eq!(sim.marker_position(), 0x01);
+    }
+
+    #[test]
+    fn tracks_eraser_separately_from_marker() {
+        let sim = SimulatedRoot::new();
+
+        sim.handle_packet(&command(vec![0x02, 0x00, 0x01, 0x01]));
+        sim.handle_packet(&command(vec![0x01, 0x08, 0x02, 0x00, 0x00, 0x00, 0x0A]));
+        sim.handle_packet(&command(vec![0x02, 0x00, 0x03, 0x02]));
+        sim.handle_packet(&command(vec![0x01, 0x08, 0x04, 0xFF, 0xFF, 0xFF, 0xF6]));
+
+        let trail = sim.trail();
+        assert_eq!(trail.len(), 2);
+        assert!(trail[0].marker_down && !trail[0].eraser_down);
+        assert!(trail[1].eraser_down && !trail[1].marker_down);
+        assert!(trail[1].points[1].y_coord.abs() < 0.01);
+    }
+
+    #[test]
+    fn reports_battery_level() {
+        let sim = SimulatedRoot::new();
+        sim.set_battery_percent(50);
+
+        let responses = sim.handle_packet(&command(vec![0x0E, 0x01, 0x01]));
+        assert_eq!(u16::from_be_bytes([responses[0][7], responses[0][8]]), 3800);
+        assert_eq!(responses[0][9], 50);
+    }
+
+    #[test]
+    fn reads_white_board_with_color_sensors() {
+        let sim = SimulatedRoot::new();
+
+        let lit = sim.handle_packet(&command(vec![0x04, 0x01, 0x01, 0x02, 0x04, 0x00]));
+        assert_eq!(&lit[0][3..5], &[0x0F, 0xFF]);
+        assert_eq!(&lit[0][17..19], &[0x0F, 0xFF]);
+
+        let dark = sim.handle_packet(&command(vec![0x04, 0x01, 0x02, 0x02, 0x00, 0x01]));
+        assert_eq!(&dark[0][3..19], &[0x00; 16]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn answers_robot_commands() {
+        let sim = SimulatedRoot::new();
+        let robot = sim.connect_running();
+
+        robot.drive_distance(100).await.unwrap();
+        robot.rotate_angle(-900).await.unwrap();
+
+        assert_pose(sim.pose(), 0.0, 100.0, 180.0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn plays_melodies_and_long_phrases() {
+        let sim = SimulatedRoot::new();
+        let robot = sim.connect_running();
+
+        robot.set_volume(40).await.unwrap();
+        let melody = Melody::new(600).note(440, 1.0).rest(0.5).note(523, 2.0);
+        robot.play_melody(&melody).await.unwrap();
+        robot.say_phrase("Hello there, how are you?").await.unwrap();
+
+        assert_eq!(sim.notes(), vec![(440, 100), (523, 200)]);
+        assert_eq!(sim.phrases(), vec!["Hello there, ", "how are you?"]);
+        assert_eq!(sim.volume(), 40);
+        assert!(matches!(
+            robot.set_volume(101).await,
+            Err(RootError::InvalidArgument(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn reports_identity_and_enabled_events() {
+        let sim = SimulatedRoot::new();
+        let robot = sim.connect_running();
+
+        robot.set_name("Drawbot 7").await.unwrap();
+        assert_eq!(robot.get_name().await.unwrap().name, "Drawbot 7");
+        assert_eq!(sim.name(), "Drawbot 7");
+        assert_eq!(
+            robot.get_serial_number().await.unwrap().serial_number,
+            "RT0000000000"
+        );
+        assert_eq!(robot.get_sku().await.unwrap().sku, "RT0-SIM");
+
+        robot
+            .disable_events(&[RootDeviceId::ColorSensor, RootDeviceId::Bumpers])
+            .await
+            .unwrap();
+        robot.enable_events(&[RootDeviceId::Bumpers]).await.unwrap();
+        let events = robot.get_enabled_events().await.unwrap();
+        assert!(!events.is_enabled(RootDeviceId::ColorSensor));
+        assert!(events.is_enabled(RootDeviceId::Bumpers));
+        assert!(events.is_enabled(RootDeviceId::TouchSensors));
+
+        assert!(matches!(
+            robot.set_name("A name that is far too long").await,
+            Err(RootError::InvalidArgument(_))
+        ));
+    }
+}
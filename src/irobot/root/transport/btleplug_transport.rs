@@ -0,0 +1,94 @@
+use async_trait::async_trait;
+use btleplug::api::{CharPropFlags, Characteristic, Peripheral as _, WriteType};
+use btleplug::platform::Peripheral;
+use futures::stream::StreamExt;
+use uuid::{uuid, Uuid};
+
+use super::{NotificationStream, RootTransport};
+
+const ROOT_RX_CHARACTERISTIC: Uuid = uuid!("6e400002-b5a3-f393-e0a9-e50e24dcca9e");
+const ROOT_TX_CHARACTERISTIC: Uuid = uuid!("6e400003-b5a3-f393-e0a9-e50e24dcca9e");
+
+// Talks to a real robot over Bluetooth Low Energy using btleplug.
+pub struct BtleplugTransport {
+    peripheral: Peripheral,
+}
+
+impl BtleplugTransport {
+    pub fn new(peripheral: Peripheral) -> BtleplugTransport {
+        BtleplugTransport { peripheral }
+    }
+
+    fn find_characteristic(
+        &self,
+        uuid: Uuid,
+        flags: CharPropFlags,
+    ) -> btleplug::Result<Characteristic> {
+        self.peripheral
+            .characteristics()
+            .into_iter()
+            .find(|c| c.uuid == uuid && c.properties.contains(flags))
+            .ok_or_else(|| {
+                btleplug::Error::NotSupported(format!("Unable to find characteristic {}", uuid))
+            })
+    }
+}
+
+#[async_trait]
+impl RootTransport for BtleplugTransport {
+    async fn connect(&self) -> btleplug::Result<()> {
+        self.peripheral.connect().await?;
+        self.peripheral.discover_services().await
+    }
+
+    async fn disconnect(&self) -> btleplug::Result<()> {
+        println!(
+            "Disconnecting from peripheral {:?}...",
+            self.peripheral.id()
+        );
+        self.peripheral.disconnect().await
+    }
+
+    async fn is_connected(&self) -> btleplug::Result<bool> {
+        self.peripheral.is_connected().await
+    }
+
+    // Notify the tx characteristic that it should send us responses
+    async fn subscribe(&self) -> btleplug::Result<()> {
+        let tx_characteristic =
+            self.find_characteristic(ROOT_TX_CHARACTERISTIC, CharPropFlags::INDICATE)?;
+        self.peripheral.subscribe(&tx_characteristic).await
+    }
+
+    async fn write_packet(&self, packet: &[u8], write_type: WriteType) -> btleplug::Result<()> {
+        // TODO: Do I need to find this each time?
+        let rx_characteristic =
+            self.find_characteristic(ROOT_RX_CHARACTERISTIC, CharPropFlags::empty())?;
+        self.peripheral
+            .write(&rx_characteristic, packet, write_type)
+            .await
+    }
+
+    async fn notifications(&self) -> btleplug::Result<NotificationStream> {
+        let notifications = self.peripheral.notifications().await?;
+        Ok(Box::pin(
+            notifications.map(|notification| notification.value),
+        ))
+    }
+
+    fn print_characteristics(&self) {
+        println!(
+            "Discover root peripheral {:?} services...",
+            self.peripheral.id()
+        );
+        for service in self.peripheral.services() {
+            println!(
+                "Service UUID {}, primary: {}",
+                service.uuid, service.primary
+            );
+            for characteristic in service.characteristics {
+                println!("  {:?}", characteristic);
+            }
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use async_trait::async_trait;
+use btleplug::api::WriteType;
+use futures::stream;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+
+use super::{NotificationStream, RootTransport};
+
+// In-memory transport with no radio involved. Every packet written by RootRobot is handed to the
+// paired LoopbackPeer, and anything the peer sends shows up as a notification from the robot.
+pub struct LoopbackTransport {
+    link: Arc<Link>,
+    written: mpsc::UnboundedSender<Vec<u8>>,
+    notifications: Arc<Notifications>,
+}
+
+// The robot side of a loopback transport, used by tests and simulators to answer packets.
+pub struct LoopbackPeer {
+    link: Arc<Link>,
+    written: mpsc::UnboundedReceiver<Vec<u8>>,
+    notifications: Arc<Notifications>,
+}
+
+// Connection state seen by both ends, so the peer can drop the link and notice it coming back
+struct Link {
+    connected: AtomicBool,
+    // Number of times connect has been called
+    connections: AtomicU32,
+}
+
+// Notifications are queued until somebody reads them rather than going to whoever is listening at the
+// time, so none are lost before the message loop subscribes or while it is between notification streams.
+struct Notifications {
+    sender: mpsc::UnboundedSender<Vec<u8>>,
+    receiver: Mutex<mpsc::UnboundedReceiver<Vec<u8>>>,
+}
+
+impl LoopbackTransport {
+    // Create a connected transport along with the peer that receives its packets
+    pub fn pair() -> (LoopbackTransport, LoopbackPeer) {
+        let (written_tx, written_rx) = mpsc::unbounded_channel();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let notifications = Arc::new(Notifications {
+            sender,
+            receiver: Mutex::new(receiver),
+        });
+        let link = Arc::new(Link {
+            connected: AtomicBool::new(true),
+            connections: AtomicU32::new(0),
+        });
+
+        (
+            LoopbackTransport {
+                link: link.clone(),
+                written: written_tx,
+                notifications: notifications.clone(),
+            },
+            LoopbackPeer {
+                link,
+                written: written_rx,
+                notifications,
+            },
+        )
+    }
+}
+
+#[async_trait]
+impl RootTransport for LoopbackTransport {
+    async fn connect(&self) -> btleplug::Result<()> {
+        self.link.connections.fetch_add(1, Ordering::SeqCst);
+        self.link.connected.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn disconnect(&self) -> btleplug::Result<()> {
+        self.link.connected.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn is_connected(&self) -> btleplug::Result<bool> {
+        Ok(self.link.connected.load(Ordering::SeqCst))
+    }
+
+    async fn subscribe(&self) -> btleplug::Result<()> {
+        Ok(())
+    }
+
+    async fn write_packet(&self, packet: &[u8], _write_type: WriteType) -> btleplug::Result<()> {
+        if !self.link.connected.load(Ordering::SeqCst) {
+            return Err(btleplug::Error::NotConnected);
+        }
+
+        self.written
+            .send(packet.to_vec())
+            .map_err(|_| btleplug::Error::NotConnected)
+    }
+
+    async fn notifications(&self) -> btleplug::Result<NotificationStream> {
+        let stream = stream::unfold(self.notifications.clone(), |notifications| async move {
+            let packet = notifications.receiver.lock().await.recv().await?;
+            Some((packet, notifications))
+        });
+        Ok(Box::pin(stream))
+    }
+
+    fn print_characteristics(&self) {
+        println!("Loopback transport, no characteristics");
+    }
+}
+
+impl LoopbackPeer {
+    // Wait for the next packet written to the transport, None once the transport is dropped
+    pub async fn next_packet(&mut self) -> Option<Vec<u8>> {
+        self.written.recv().await
+    }
+
+    // Drop the link from the robot's end, as if it went out of range
+    pub fn drop_connection(&self) {
+        self.link.connected.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.link.connected.load(Ordering::SeqCst)
+    }
+
+    // Number of times the transport has connected since being created, not counting the first connection
+    pub fn connections(&self) -> u32 {
+        self.link.connections.load(Ordering::SeqCst)
+    }
+
+    // Send a packet back as if it came from the robot
+    pub fn notify(&self, packet: Vec<u8>) {
+        // The receiving end lives as long as the sender, so this can't fail
+        let _ = self.notifications.sender.send(packet);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream::StreamExt;
+
+    #[tokio::test]
+    async fn can_pass_packets_both_ways() {
+        let (transport, mut peer) = LoopbackTransport::pair();
+        let mut notifications = transport.notifications().await.unwrap();
+
+        transport
+            .write_packet(&[0x01, 0x02], WriteType::WithResponse)
+            .await
+            .unwrap();
+        assert_eq!(peer.next_packet().await, Some(vec![0x01, 0x02]));
+
+        peer.notify(vec![0x03, 0x04]);
+        assert_eq!(notifications.next().await, Some(vec![0x03, 0x04]));
+    }
+
+    #[tokio::test]
+    async fn holds_notifications_until_subscribed() {
+        let (transport, peer) = LoopbackTransport::pair();
+
+        peer.notify(vec![0x05]);
+        let mut notifications = transport.notifications().await.unwrap();
+        peer.notify(vec![0x06]);
+
+        assert_eq!(notifications.next().await, Some(vec![0x05]));
+        assert_eq!(notifications.next().await, Some(vec![0x06]));
+    }
+
+    #[tokio::test]
+    async fn peer_can_drop_connection() {
+        let (transport, peer) = LoopbackTransport::pair();
+
+        peer.drop_connection();
+        assert!(!transport.is_connected().await.unwrap());
+
+        transport.connect().await.unwrap();
+        assert!(transport.is_connected().await.unwrap());
+        assert_eq!(peer.connections(), 1);
+    }
+
+    #[tokio::test]
+    async fn write_fails_when_disconnected() {
+        let (transport, _peer) = LoopbackTransport::pair();
+        transport.disconnect().await.unwrap();
+
+        assert!(!transport.is_connected().await.unwrap());
+        assert!(transport
+            .write_packet(&[0x01], WriteType::WithResponse)
+            .await
+            .is_err());
+    }
+}
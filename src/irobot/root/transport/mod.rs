@@ -0,0 +1,35 @@
+use async_trait::async_trait;
+use btleplug::api::WriteType;
+use futures::stream::Stream;
+use std::pin::Pin;
+
+mod btleplug_transport;
+pub use self::btleplug_transport::BtleplugTransport;
+
+mod loopback_transport;
+pub use self::loopback_transport::LoopbackPeer;
+pub use self::loopback_transport::LoopbackTransport;
+
+// Stream of raw packets received from the robot
+pub type NotificationStream = Pin<Box<dyn Stream<Item = Vec<u8>> + Send>>;
+
+// The link between RootRobot and a physical (or pretend) robot. Packets written here are already
+// padded and CRC checked, notifications are the raw 20 byte packets sent back by the robot.
+#[async_trait]
+pub trait RootTransport: Send + Sync {
+    async fn connect(&self) -> btleplug::Result<()>;
+
+    async fn disconnect(&self) -> btleplug::Result<()>;
+
+    async fn is_connected(&self) -> btleplug::Result<bool>;
+
+    // Ask the robot to start sending us responses and events
+    async fn subscribe(&self) -> btleplug::Result<()>;
+
+    async fn write_packet(&self, packet: &[u8], write_type: WriteType) -> btleplug::Result<()>;
+
+    async fn notifications(&self) -> btleplug::Result<NotificationStream>;
+
+    // Print out any details about the underlying link, useful for debugging
+    fn print_characteristics(&self);
+}
@@ -0,0 +1,3 @@
+pub mod irobot;
+pub mod orchestrator;
+pub mod utils;
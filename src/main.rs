@@ -1,43 +1,84 @@
-use std::{error::Error, sync::Arc};
+use std::{env, error::Error, fs, sync::Arc, time::Duration};
 
-mod utils;
-mod orchestrator;
+use root_commander::irobot::root::RootRobot;
+use root_commander::orchestrator::{
+    dry_run_svg, LightAnimation, LightAnimator, LinearOrchestrator,
+};
+use root_commander::utils::{find_root_peripheral, Point};
 
-mod irobot;
-use irobot::root::{LEDLightsState, RootRobot};
+fn heart() -> Vec<Vec<Point>> {
+    vec![
+        vec![Point::new(0.0, -30.0), Point::new(-40.0, 30.0)],
+        vec![
+            Point::new(-40.0, 30.0),
+            Point::new(-20.0, 50.0),
+            Point::new(0.0, 30.0),
+        ],
+        vec![
+            Point::new(0.0, 30.0),
+            Point::new(20.0, 50.0),
+            Point::new(40.0, 30.0),
+        ],
+        vec![Point::new(40.0, 30.0), Point::new(0.0, -30.0)],
+    ]
+}
 
-use orchestrator::{LinearOrchestrator};
-use utils::{find_root_peripheral, Point};
+// Draw the heart then say something about it, showing how it is going on the lights
+async fn draw_heart(robot: &RootRobot, lights: &LightAnimator) -> Result<(), Box<dyn Error>> {
+    lights.play(LightAnimation::drawing());
+    let mut orch = LinearOrchestrator::new();
+    if let Err(err) = orch.orchestrate(robot, heart()).await {
+        // Leave the error showing for a moment before the robot is shut down
+        lights.play(LightAnimation::error());
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        return Err(err.into());
+    }
+    lights.play(LightAnimation::connected());
+
+    robot.say_phrase("What").await?;
+    robot.say_phrase("are").await?;
+    robot.say_phrase("you").await?;
+    robot.say_phrase("doing?").await?;
+    Ok(())
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
+    // Passing --dry-run <file> renders the drawing to an SVG against a simulated robot instead
+    let args: Vec<String> = env::args().collect();
+    if let Some(index) = args.iter().position(|arg| arg == "--dry-run") {
+        let path = args
+            .get(index + 1)
+            .ok_or("--dry-run needs an output file")?;
+        fs::write(path, dry_run_svg(heart()).await?)?;
+        println!("Wrote dry run to {}", path);
+        return Ok(());
+    }
+
     // Get a coonection to the device
     // Note that we store it in an arc so that it can be shared to the message loop
-    let root_peripheral: Arc<RootRobot> = Arc::new(
-        find_root_peripheral()
-            .await
-            .expect("Couldnt find root robot!"),
-    );
+    let root_peripheral: Arc<RootRobot> = Arc::new(find_root_peripheral().await?);
 
     // Print out the characteristics
-    root_peripheral.print_characteristics().await;
+    root_peripheral.print_characteristics();
 
     // Subscribe to the BLE channel to start receiving messages
-    root_peripheral.subscribe().await;
+    root_peripheral.subscribe().await?;
 
     let p_clone = root_peripheral.clone();
     // In a background thread enter a loop which reads any messages received from the device
-    tokio::spawn(async move {
-        p_clone.run_message_loop().await;
+    let message_loop = tokio::spawn(async move {
+        if let Err(err) = p_clone.run_message_loop().await {
+            eprintln!("Message loop stopped: {}", err);
+        }
     });
 
     // Send a message to the device requesting the current versions
-    root_peripheral.print_versions().await;
+    root_peripheral.print_versions().await?;
 
     // Turn on the lights
-    root_peripheral
-        .set_lights(LEDLightsState::Spin, 0x00, 0xFF, 0x00)
-        .await;
+    let lights = LightAnimator::new(root_peripheral.clone());
+    lights.play(LightAnimation::connected());
 
     // Draw letter H
     // orchestrator::orchestrate(
@@ -108,31 +149,14 @@ async fn main() -> Result<(), Box<dyn Error>> {
     // )
     // .await;
 
-    // Draw Heart
-    let mut orch = LinearOrchestrator::new();
-    orch.orchestrate(
-        &root_peripheral,
-        vec![
-            vec![Point::new(0.0, -30.0), Point::new(-40.0, 30.0)],
-            vec![
-                Point::new(-40.0, 30.0),
-                Point::new(-20.0, 50.0),
-                Point::new(0.0, 30.0),
-            ],
-            vec![
-                Point::new(0.0, 30.0),
-                Point::new(20.0, 50.0),
-                Point::new(40.0, 30.0),
-            ],
-            vec![Point::new(40.0, 30.0), Point::new(0.0, -30.0)],
-        ],
-    )
-    .await;
-
-    root_peripheral.say_phrase("What").await;
-    root_peripheral.say_phrase("are").await;
-    root_peripheral.say_phrase("you").await;
-    root_peripheral.say_phrase("doing?").await;
+    // Draw Heart, stopping part way through if Ctrl-C is pressed
+    let result = tokio::select! {
+        result = draw_heart(&root_peripheral, &lights) => result,
+        _ = tokio::signal::ctrl_c() => {
+            println!("Interrupted, shutting down");
+            Ok(())
+        }
+    };
 
     // Start with a small movement
     //root_peripheral.drive_distance(10).await;
@@ -141,8 +165,11 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     //designs::draw_letter_H(&root_peripheral).await;
 
-    // Disconnect
-    root_peripheral.disconnect().await;
+    // Stop the lights first so nothing else gets sent, then leave the robot stopped with the marker up,
+    // disconnect and wait for the message loop to finish
+    lights.stop();
+    root_peripheral.shutdown().await?;
+    message_loop.await?;
 
-    Ok(())
+    result
 }
@@ -0,0 +1,434 @@
+use super::{DrawingJob, RootFleet};
+use crate::{
+    irobot::root::RootError,
+    utils::{calculate_distance, Point},
+};
+
+// Half the width of a Root plus some room to spare. Robots whose paths come closer than twice this could
+// bump into each other, so they are never given overlapping workspaces at the same time.
+const ROBOT_RADIUS_MM: f32 = 90.0;
+
+// Where a robot has been put down on the board before it starts
+#[derive(Clone, Debug, PartialEq)]
+pub struct RobotStart {
+    pub robot: String,
+    pub position: Point,
+    pub heading: f32, // in degrees clockwise from the board's +y, the same as LinearOrchestrator
+}
+
+// The strokes one robot draws, already moved into that robot's own frame where it starts at (0, 0) facing +y
+#[derive(Clone, Debug, PartialEq)]
+pub struct RobotShare {
+    pub robot: String,
+    pub points: Vec<Vec<Point>>,
+    // Corners of the area of the board the robot's center stays within, including where it starts
+    pub workspace: (Point, Point),
+    // Shares in the same phase are drawn at the same time, phases are drawn one after another
+    pub phase: usize,
+}
+
+// One design split between several robots on the same board. The design is cut into strips across x with
+// about the same amount of drawing in each, the strips go to the robots in order of where they start, and
+// any robots whose workspaces would still overlap are put into different phases. Robots wait where they start
+// until their phase and stay where they finish after it, so no workspace may come near another robot's start
+// or finish.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CollaborativePlan {
+    pub shares: Vec<RobotShare>,
+}
+
+impl CollaborativePlan {
+    // Plan a design given in board coordinates, in the same format LinearOrchestrator::orchestrate takes.
+    // A single point line carries on from the end of the line before, or from the board origin if it comes first.
+    // Robots which end up with nothing to draw are left out, but still count as sitting where they start.
+    pub fn new(
+        points: &[Vec<Point>],
+        starts: &[RobotStart],
+    ) -> Result<CollaborativePlan, RootError> {
+        if starts.is_empty() {
+            return Err(RootError::InvalidArgument(
+                "Need at least one robot to draw with".to_string(),
+            ));
+        }
+        for (index, start) in starts.iter().enumerate() {
+            if starts[..index]
+                .iter()
+                .any(|other| other.robot == start.robot)
+            {
+                return Err(RootError::InvalidArgument(format!(
+                    "{:?} is given more than one start",
+                    start.robot
+                )));
+            }
+        }
+
+        let strokes = standalone_strokes(points);
+        let strips = split_into_strips(&strokes, starts.len());
+
+        let mut starts: Vec<&RobotStart> = starts.iter().collect();
+        starts.sort_by(|a, b| a.position.x_coord.total_cmp(&b.position.x_coord));
+
+        // Every spot a robot sits still at while others are drawing
+        let mut resting: Vec<(&str, Point)> = starts
+            .iter()
+            .map(|start| (start.robot.as_str(), start.position))
+            .collect();
+
+        let mut shares: Vec<RobotShare> = vec![];
+        for (start, strip) in starts.into_iter().zip(strips) {
+            if strip.is_empty() {
+                continue;
+            }
+            let board_points: Vec<Vec<Point>> =
+                strip.iter().map(|index| strokes[*index].clone()).collect();
+
+            let workspace = bounding_box(
+                board_points
+                    .iter()
+                    .flatten()
+                    .chain(std::iter::once(&start.position)),
+            );
+            let phase = (0..)
+                .find(|phase| {
+                    !shares.iter().any(|share| {
+                        share.phase == *phase && workspaces_overlap(&share.workspace, &workspace)
+                    })
+                })
+                .unwrap();
+            resting.push((&start.robot, *board_points.last().unwrap().last().unwrap()));
+
+            shares.push(RobotShare {
+                robot: start.robot.clone(),
+                points: board_points
+                    .iter()
+                    .map(|line| {
+                        line.iter()
+                            .map(|point| to_robot_frame(start, point))
+                            .collect()
+                    })
+                    .collect(),
+                workspace,
+                phase,
+            });
+        }
+
+        for share in &shares {
+            for (robot, spot) in &resting {
+                if *robot != share.robot && workspaces_overlap(&share.workspace, &(*spot, *spot)) {
+                    return Err(RootError::InvalidArgument(format!(
+                        "{} would have to drive too close to {} waiting at ({}, {})",
+                        share.robot, robot, spot.x_coord, spot.y_coord
+                    )));
+                }
+            }
+        }
+        Ok(CollaborativePlan { shares })
+    }
+
+    pub fn phases(&self) -> usize {
+        self.shares
+            .iter()
+            .map(|share| share.phase + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Draw each phase in turn, with every share in a phase drawn at the same time by the fleet robot of the
+    // same name. Every robot is checked for before anything is drawn, and if anything goes wrong in a phase
+    // the later phases are not started.
+    pub async fn run(
+        &self,
+        fleet: &RootFleet,
+    ) -> Result<Vec<(DrawingJob, Result<(), RootError>)>, RootError> {
+        if let Some(share) = self
+            .shares
+            .iter()
+            .find(|share| fleet.robot(&share.robot).is_none())
+        {
+            return Err(RootError::InvalidArgument(format!(
+                "No robot named {:?}",
+                share.robot
+            )));
+        }
+
+        let mut results = vec![];
+        for phase in 0..self.phases() {
+            let jobs = self
+                .shares
+                .iter()
+                .filter(|share| share.phase == phase)
+                .map(|share| DrawingJob::new(share.robot.clone(), share.points.clone()))
+                .collect();
+
+            let phase_results = fleet.draw(jobs).await?;
+            let failed = phase_results.iter().any(|(_, result)| result.is_err());
+            results.extend(phase_results);
+            if failed {
+                break;
+            }
+        }
+        Ok(results)
+    }
+}
+
+// Give every line its own starting point so lines can be handed to different robots
+fn standalone_strokes(points: &[Vec<Point>]) -> Vec<Vec<Point>> {
+    let mut current = Point::new(0.0, 0.0);
+    let mut strokes = vec![];
+    for line in points.iter().filter(|line| !line.is_empty()) {
+        let stroke = if line.len() == 1 {
+            vec![current, line[0]]
+        } else {
+            line.clone()
+        };
+        current = *stroke.last().unwrap();
+        strokes.push(stroke);
+    }
+    strokes
+}
+
+// Group strokes into strips from left to right with about the same length of line in each, returning the
+// indexes of the strokes in each strip in the order they were given
+fn split_into_strips(strokes: &[Vec<Point>], count: usize) -> Vec<Vec<usize>> {
+    let lengths: Vec<f32> = strokes
+        .iter()
+        .map(|stroke| {
+            stroke
+                .windows(2)
+                .map(|pair| calculate_distance(&pair[0], &pair[1]))
+                .sum()
+        })
+        .collect();
+    let total: f32 = lengths.iter().sum();
+
+    let mut by_x: Vec<usize> = (0..strokes.len()).collect();
+    by_x.sort_by(|a, b| {
+        let (a, b) = (bounding_box(&strokes[*a]), bounding_box(&strokes[*b]));
+        (a.0.x_coord + a.1.x_coord).total_cmp(&(b.0.x_coord + b.1.x_coord))
+    });
+
+    let mut strips = vec![vec![]; count];
+    let mut drawn = 0.0;
+    for (position, index) in by_x.into_iter().enumerate() {
+        // Strips are cut by length where there is any, falling back on the number of strokes for dots
+        let share = if total > 0.0 {
+            (drawn + lengths[index] / 2.0) / total
+        } else {
+            (position as f32 + 0.5) / strokes.len() as f32
+        };
+        drawn += lengths[index];
+        strips[((share * count as f32) as usize).min(count - 1)].push(index);
+    }
+
+    for strip in strips.iter_mut() {
+        strip.sort();
+    }
+    strips
+}
+
+fn bounding_box<'a>(points: impl IntoIterator<Item = &'a Point>) -> (Point, Point) {
+    points.into_iter().fold(
+        (
+            Point::new(f32::INFINITY, f32::INFINITY),
+            Point::new(f32::NEG_INFINITY, f32::NEG_INFINITY),
+        ),
+        |(min, max), point| {
+            (
+                Point::new(
+                    min.x_coord.min(point.x_coord),
+                    min.y_coord.min(point.y_coord),
+                ),
+                Point::new(
+                    max.x_coord.max(point.x_coord),
+                    max.y_coord.max(point.y_coord),
+                ),
+            )
+        },
+    )
+}
+
+fn workspaces_overlap(a: &(Point, Point), b: &(Point, Point)) -> bool {
+    let gap = 2.0 * ROBOT_RADIUS_MM;
+    a.0.x_coord < b.1.x_coord + gap
+        && b.0.x_coord < a.1.x_coord + gap
+        && a.0.y_coord < b.1.y_coord + gap
+        && b.0.y_coord < a.1.y_coord + gap
+}
+
+// Move a point on the board into the frame of a robot, where it starts at (0, 0) facing +y
+fn to_robot_frame(start: &RobotStart, point: &Point) -> Point {
+    let x = point.x_coord - start.position.x_coord;
+    let y = point.y_coord - start.position.y_coord;
+    // The robot is turned clockwise on the board, so the board is turned anticlockwise as the robot sees it
+    let (sin, cos) = start.heading.to_radians().sin_cos();
+    Point::new(x * cos - y * sin, x * sin + y * cos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irobot::root::SimulatedRoot;
+
+    fn start(robot: &str, x: f32, y: f32, heading: f32) -> RobotStart {
+        RobotStart {
+            robot: robot.to_string(),
+            position: Point::new(x, y),
+            heading,
+        }
+    }
+
+    fn assert_point(point: &Point, x: f32, y: f32) {
+        assert!(
+            (point.x_coord - x).abs() < 0.01 && (point.y_coord - y).abs() < 0.01,
+            "{:?} is not ({}, {})",
+            point,
+            x,
+            y
+        );
+    }
+
+    #[test]
+    fn moves_points_into_robot_frame() {
+        // Facing along the board's +x, so a point further along x is straight ahead
+        let point = to_robot_frame(&start("root", 100.0, 0.0, 90.0), &Point::new(150.0, 0.0));
+        assert_point(&point, 0.0, 50.0);
+    }
+
+    #[test]
+    fn splits_far_apart_strokes_into_one_phase() {
+        let design = vec![
+            vec![Point::new(1000.0, 0.0), Point::new(1000.0, 150.0)],
+            vec![Point::new(0.0, 0.0), Point::new(0.0, 100.0)],
+            vec![Point::new(50.0, 100.0)],
+        ];
+        let plan = CollaborativePlan::new(
+            &design,
+            &[
+                start("right", 1000.0, 0.0, 0.0),
+                start("left", 0.0, 0.0, 0.0),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(plan.phases(), 1);
+        let left = &plan.shares[0];
+        assert_eq!(left.robot, "left");
+        assert_eq!(left.points.len(), 2);
+        // The single point line got the end of the line before as its start
+        assert_point(&left.points[1][0], 0.0, 100.0);
+        assert_point(&left.points[1][1], 50.0, 100.0);
+
+        let right = &plan.shares[1];
+        assert_eq!(right.robot, "right");
+        assert_point(&right.points[0][0], 0.0, 0.0);
+        assert_point(&right.points[0][1], 0.0, 150.0);
+    }
+
+    #[test]
+    fn puts_overlapping_workspaces_in_different_phases() {
+        // Both robots pass near the origin on the way to their strokes, but never near where the other waits
+        let design = vec![
+            vec![Point::new(300.0, 600.0), Point::new(300.0, 700.0)],
+            vec![Point::new(350.0, -600.0), Point::new(350.0, -700.0)],
+        ];
+        let plan = CollaborativePlan::new(
+            &design,
+            &[start("a", 0.0, 0.0, 0.0), start("b", 600.0, 0.0, 0.0)],
+        )
+        .unwrap();
+
+        assert_eq!(plan.shares.len(), 2);
+        assert_eq!(plan.phases(), 2);
+        assert!(CollaborativePlan::new(&design, &[]).is_err());
+    }
+
+    #[test]
+    fn rejects_workspaces_reaching_another_robot() {
+        // Whichever robot draws first would have to drive over the other one waiting to start
+        let design = vec![
+            vec![Point::new(0.0, 0.0), Point::new(100.0, 0.0)],
+            vec![Point::new(150.0, 0.0), Point::new(250.0, 0.0)],
+        ];
+        let result = CollaborativePlan::new(
+            &design,
+            &[start("a", 0.0, -50.0, 0.0), start("b", 250.0, -50.0, 0.0)],
+        );
+        assert!(matches!(result, Err(RootError::InvalidArgument(_))));
+
+        // A robot given nothing to draw is still in the way
+        let result = CollaborativePlan::new(
+            &design[..1],
+            &[
+                start("a", 0.0, -50.0, 0.0),
+                start("idle", 250.0, -50.0, 0.0),
+            ],
+        );
+        assert!(matches!(result, Err(RootError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn rejects_robot_started_twice() {
+        let design = vec![vec![Point::new(0.0, 0.0), Point::new(0.0, 100.0)]];
+        let result = CollaborativePlan::new(
+            &design,
+            &[start("a", 0.0, 0.0, 0.0), start("a", 1000.0, 0.0, 0.0)],
+        );
+        assert!(matches!(result, Err(RootError::InvalidArgument(_))));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn checks_every_robot_before_drawing() {
+        let sim = SimulatedRoot::new();
+        let mut fleet = RootFleet::new();
+        fleet.add("first", sim.connect()).await.unwrap();
+
+        // The missing robot only draws in the second phase, the first must not start without it
+        let design = vec![
+            vec![Point::new(300.0, 600.0), Point::new(300.0, 700.0)],
+            vec![Point::new(350.0, -600.0), Point::new(350.0, -700.0)],
+        ];
+        let plan = CollaborativePlan::new(
+            &design,
+            &[
+                start("first", 0.0, 0.0, 0.0),
+                start("missing", 600.0, 0.0, 0.0),
+            ],
+        )
+        .unwrap();
+        assert_eq!(plan.phases(), 2);
+
+        let result = plan.run(&fleet).await;
+        assert!(matches!(result, Err(RootError::InvalidArgument(_))));
+        assert!(sim.trail().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn runs_each_share_on_its_robot() {
+        let (left, right) = (SimulatedRoot::new(), SimulatedRoot::new());
+        let mut fleet = RootFleet::new();
+        fleet.add("left", left.connect()).await.unwrap();
+        fleet.add("right", right.connect()).await.unwrap();
+
+        let design = vec![
+            vec![Point::new(0.0, 0.0), Point::new(0.0, 60.0)],
+            vec![Point::new(1000.0, 0.0), Point::new(1040.0, 0.0)],
+        ];
+        let plan = CollaborativePlan::new(
+            &design,
+            &[
+                start("left", 0.0, 0.0, 0.0),
+                start("right", 1000.0, 0.0, 90.0),
+            ],
+        )
+        .unwrap();
+        let results = plan.run(&fleet).await.unwrap();
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+
+        // Each robot drew straight ahead of where it started
+        for (sim, length) in [(left, 60.0), (right, 40.0)] {
+            let trail = sim.trail();
+            let drawn = trail.iter().find(|segment| segment.marker_down).unwrap();
+            assert_point(drawn.points.last().unwrap(), 0.0, length);
+        }
+    }
+}
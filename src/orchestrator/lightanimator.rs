@@ -0,0 +1,187 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+
+use crate::irobot::root::{LEDLightsState, RootRobot};
+
+// How long each frame of the status animations is shown
+const STATUS_FRAME: Duration = Duration::from_millis(500);
+
+// One step of a light animation, held for its duration before moving on to the next
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LightFrame {
+    pub state: LEDLightsState,
+    pub color: (u8, u8, u8),
+    pub duration: Duration,
+}
+
+// A sequence of light states and colors, built up one frame at a time:
+// LightAnimation::new(true).frame(LEDLightsState::On, (255, 0, 0), Duration::from_millis(200))
+#[derive(Clone, Debug, PartialEq)]
+pub struct LightAnimation {
+    pub frames: Vec<LightFrame>,
+    // Start again from the first frame after the last, rather than holding the last frame
+    pub repeat: bool,
+}
+
+impl LightAnimation {
+    pub fn new(repeat: bool) -> LightAnimation {
+        LightAnimation {
+            frames: vec![],
+            repeat,
+        }
+    }
+
+    pub fn frame(
+        mut self,
+        state: LEDLightsState,
+        color: (u8, u8, u8),
+        duration: Duration,
+    ) -> LightAnimation {
+        self.frames.push(LightFrame {
+            state,
+            color,
+            duration,
+        });
+        self
+    }
+
+    // Steady green once connected
+    pub fn connected() -> LightAnimation {
+        LightAnimation::new(false).frame(LEDLightsState::On, (0x00, 0xFF, 0x00), STATUS_FRAME)
+    }
+
+    // Spinning blue while drawing
+    pub fn drawing() -> LightAnimation {
+        LightAnimation::new(false).frame(LEDLightsState::Spin, (0x00, 0x00, 0xFF), STATUS_FRAME)
+    }
+
+    // Flashing between red and off when something has gone wrong
+    pub fn error() -> LightAnimation {
+        LightAnimation::new(true)
+            .frame(LEDLightsState::On, (0xFF, 0x00, 0x00), STATUS_FRAME)
+            .frame(LEDLightsState::Off, (0x00, 0x00, 0x00), STATUS_FRAME)
+    }
+
+    // Fades from red at the start to green once done, for a fraction done between 0 and 1
+    pub fn progress(fraction: f32) -> LightAnimation {
+        let fraction = fraction.clamp(0.0, 1.0);
+        let green = (fraction * 255.0).round() as u8;
+        LightAnimation::new(false).frame(
+            LEDLightsState::On,
+            (0xFF - green, green, 0x00),
+            STATUS_FRAME,
+        )
+    }
+}
+
+// Plays light animations in the background so they never hold up motion commands. Setting the lights
+// never waits on the robot, and starting a new animation replaces whatever was playing.
+pub struct LightAnimator {
+    robot: Arc<RootRobot>,
+    playing: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl LightAnimator {
+    pub fn new(robot: Arc<RootRobot>) -> LightAnimator {
+        LightAnimator {
+            robot,
+            playing: Mutex::new(None),
+        }
+    }
+
+    // Start playing an animation, the last frame is left showing once a non repeating animation is done.
+    // Must be called from within a tokio runtime as the animation runs as a task.
+    pub fn play(&self, animation: LightAnimation) {
+        let robot = self.robot.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                for (index, frame) in animation.frames.iter().enumerate() {
+                    let (r, g, b) = frame.color;
+                    if let Err(err) = robot.set_lights(frame.state, r, g, b).await {
+                        eprintln!("Stopping light animation: {}", err);
+                        return;
+                    }
+                    // No need to wait out the last frame when it is going to be left showing
+                    if !animation.repeat && index + 1 == animation.frames.len() {
+                        return;
+                    }
+                    tokio::time::sleep(frame.duration).await;
+                }
+                if animation.frames.is_empty() {
+                    return;
+                }
+            }
+        });
+
+        if let Some(previous) = self.playing.lock().unwrap().replace(task) {
+            previous.abort();
+        }
+    }
+
+    // Stop whatever is playing, leaving the lights as they are
+    pub fn stop(&self) {
+        if let Some(playing) = self.playing.lock().unwrap().take() {
+            playing.abort();
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|playing| !playing.is_finished())
+    }
+}
+
+impl Drop for LightAnimator {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irobot::root::SimulatedRoot;
+
+    #[test]
+    fn progress_fades_from_red_to_green() {
+        assert_eq!(
+            LightAnimation::progress(0.0).frames[0].color,
+            (0xFF, 0x00, 0x00)
+        );
+        assert_eq!(
+            LightAnimation::progress(1.0).frames[0].color,
+            (0x00, 0xFF, 0x00)
+        );
+        assert_eq!(
+            LightAnimation::progress(2.0).frames[0].color,
+            (0x00, 0xFF, 0x00)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn animates_without_blocking_motion() {
+        let sim = SimulatedRoot::new();
+        let robot = sim.connect_running();
+
+        let animator = LightAnimator::new(robot.clone());
+        let frame = Duration::from_millis(10);
+        animator.play(
+            LightAnimation::new(true)
+                .frame(LEDLightsState::On, (0xFF, 0x00, 0x00), frame)
+                .frame(LEDLightsState::Blink, (0x00, 0x00, 0xFF), frame),
+        );
+        robot.drive_distance(100).await.unwrap();
+        assert!(animator.is_playing());
+
+        animator.play(LightAnimation::connected());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        robot.get_position().await.unwrap();
+        assert_eq!(sim.lights(), (0x01, 0x00, 0xFF, 0x00));
+        assert!(!animator.is_playing());
+    }
+}
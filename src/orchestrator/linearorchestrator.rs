@@ -1,9 +1,65 @@
-use crate::{irobot::root::{MarkerPosition, RootRobot}, utils::{Point, calculate_distance, calculate_angle, calculate_radius_and_center, calculate_degrees_of_rotation}};
+use crate::{
+    irobot::root::{
+        messages::Orientation, GravityCompensation, MarkerPosition, RootError, RootRobot,
+    },
+    utils::{
+        calculate_angle, calculate_degrees_of_rotation, calculate_distance,
+        calculate_radius_and_center, Point,
+    },
+};
+
+// How far the robot's reported pose ended up from where a stroke meant to leave it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StrokeReport {
+    pub target: Point,
+    pub reported: Point,
+    pub marker_down: bool,
+    pub position_error: f32, // in mm
+    pub heading_error: f32,  // in degrees, positive when the robot ended up clockwise of the target
+}
 
 pub struct LinearOrchestrator {
     current_x_coord: f32,
     current_y_coord: f32,
     current_heading: f32, // in degrees
+    use_navigation: bool,
+    stroke_reports: Vec<StrokeReport>,
+    tool: MarkerPosition, // what gets put down for strokes, the marker unless erasing
+    auto_gravity_compensation: bool,
+    // Where the robot's own (0, 0) is in the drawing and which way its +y points, in degrees. The robot
+    // resets its position on every new connection, which moves this to wherever it was at the time.
+    origin_x_coord: f32,
+    origin_y_coord: f32,
+    origin_heading: f32,
+    // Connection the origin was last lined up with, see robot.session()
+    session: u32,
+    // Where a motion the robot was sent but never answered because the link dropped was taking it, and
+    // which way it would be facing. The robot carries the motion out anyway.
+    unanswered_pose: Option<(Point, f32)>,
+    // Strokes of the line being drawn which are finished, an interrupted line carries on after them
+    strokes_drawn: usize,
+}
+
+// Rough battery use while drawing, erring on the side of needing more
+const BATTERY_PERCENT_PER_METRE: f32 = 1.0;
+// Charge to still have left once a drawing is finished
+const BATTERY_RESERVE_PERCENT: f32 = 10.0;
+
+// Gravity compensation used on walls, in decipercent, the same as the robot's own default
+const WALL_GRAVITY_COMPENSATION: u16 = 500;
+
+// Distance between passes when sweeping the eraser over an area, a little under the width of the eraser pad
+const ERASER_SWEEP_SPACING_MM: f32 = 15.0;
+
+// The orchestrator measures heading clockwise from positive y, the robot measures it counter-clockwise from
+// positive x. Both share the same x and y as long as the robot's position was reset where the drawing starts,
+// otherwise positions go through the orchestrator's origin first.
+fn to_robot_heading(heading: f32) -> i16 {
+    ((90.0 - heading).rem_euclid(360.0) * 10.0).round() as i16 % 3600
+}
+
+fn from_robot_heading(heading_deci_degrees: i16) -> f32 {
+    90.0 - heading_deci_degrees as f32 / 10.0
 }
 
 impl LinearOrchestrator {
@@ -12,25 +68,207 @@ impl LinearOrchestrator {
             current_x_coord: 0.0,
             current_y_coord: 0.0,
             current_heading: 0.0,
+            use_navigation: false,
+            stroke_reports: vec![],
+            tool: MarkerPosition::MarkerDown,
+            auto_gravity_compensation: false,
+            origin_x_coord: 0.0,
+            origin_y_coord: 0.0,
+            origin_heading: 0.0,
+            session: 0,
+            unanswered_pose: None,
+            strokes_drawn: 0,
+        }
+    }
+
+    // When enabled straight lines and turns are sent as Navigate to Position commands with absolute
+    // coordinates and heading, so the robot's own odometry corrects any drift instead of it building up.
+    pub fn set_use_navigation(&mut self, use_navigation: bool) {
+        self.use_navigation = use_navigation;
+    }
+
+    // When enabled each drawing starts by checking whether the robot is on a wall or a table and
+    // setting gravity compensation to match
+    pub fn set_auto_gravity_compensation(&mut self, auto_gravity_compensation: bool) {
+        self.auto_gravity_compensation = auto_gravity_compensation;
+    }
+
+    // Turn gravity compensation on when the robot is on a wall and off when it is flat. If it is
+    // somewhere in between the setting is left alone.
+    pub async fn configure_for_surface(&self, robot: &RootRobot) -> Result<Orientation, RootError> {
+        robot.enable_accelerometer().await?;
+        let orientation = robot.get_accelerometer().await?.orientation();
+        println!("Robot is {:?}", orientation);
+
+        match orientation {
+            Orientation::Vertical => {
+                robot
+                    .set_gravity_compensation(GravityCompensation::On, WALL_GRAVITY_COMPENSATION)
+                    .await?
+            }
+            Orientation::Horizontal => {
+                robot
+                    .set_gravity_compensation(GravityCompensation::Off, WALL_GRAVITY_COMPENSATION)
+                    .await?
+            }
+            Orientation::Tilted => {}
+        }
+        Ok(orientation)
+    }
+
+    // Error between each stroke's target and where the robot reported it actually finished, in drawing order
+    pub fn stroke_reports(&self) -> &[StrokeReport] {
+        &self.stroke_reports
+    }
+
+    // Replace our assumed pose with the one the robot reported at the end of a motion, so the next move
+    // is worked out from where the robot really is and corrects for any drift so far
+    fn reconcile(&mut self, x_coord: i32, y_coord: i32, heading_deci_degrees: i16) {
+        let (sin, cos) = self.origin_heading.to_radians().sin_cos();
+        let (x_coord, y_coord) = (x_coord as f32, y_coord as f32);
+        self.current_x_coord = self.origin_x_coord + x_coord * cos + y_coord * sin;
+        self.current_y_coord = self.origin_y_coord - x_coord * sin + y_coord * cos;
+        self.current_heading =
+            (from_robot_heading(heading_deci_degrees) + self.origin_heading).rem_euclid(360.0);
+        self.unanswered_pose = None;
+    }
+
+    // Note where a motion was taking the robot if the link dropped after the robot was sent it. Sending
+    // failing outright means the robot never got it, so it stays wherever it last reported.
+    fn motion_interrupted(
+        &mut self,
+        err: RootError,
+        destination: Point,
+        heading: f32,
+    ) -> RootError {
+        if matches!(err, RootError::ConnectionLost) {
+            self.unanswered_pose = Some((destination, heading.rem_euclid(360.0)));
         }
+        err
+    }
+
+    // A stroke is done once the robot has finished it, or been sent it before the link dropped
+    fn count_stroke<T>(&mut self, result: &Result<T, RootError>) {
+        if matches!(result, Ok(_) | Err(RootError::ConnectionLost)) {
+            self.strokes_drawn += 1;
+        }
+    }
+
+    // Called before every motion, so nothing is worked out against an origin from before the robot
+    // last reconnected
+    fn follow_session(&mut self, robot: &RootRobot) {
+        if robot.session() != self.session {
+            self.session = robot.session();
+            self.rebase_origin();
+        }
+    }
+
+    // The robot has just reset its position on a new connection, so its (0, 0) is now wherever it was
+    // last seen in the drawing, or where it was sent to if the link dropped before it answered
+    fn rebase_origin(&mut self) {
+        if let Some((position, heading)) = self.unanswered_pose.take() {
+            self.current_x_coord = position.x_coord;
+            self.current_y_coord = position.y_coord;
+            self.current_heading = heading;
+        }
+        self.origin_x_coord = self.current_x_coord;
+        self.origin_y_coord = self.current_y_coord;
+        self.origin_heading = self.current_heading;
+    }
+
+    // Turn a point in the drawing into the robot's own coordinates
+    fn to_robot_position(&self, point: &Point) -> (i32, i32) {
+        let (sin, cos) = self.origin_heading.to_radians().sin_cos();
+        let x_coord = point.x_coord - self.origin_x_coord;
+        let y_coord = point.y_coord - self.origin_y_coord;
+        (
+            (x_coord * cos - y_coord * sin).round() as i32,
+            (x_coord * sin + y_coord * cos).round() as i32,
+        )
+    }
+
+    // Compare the reconciled pose against what a stroke was aiming for
+    fn record_stroke(&mut self, target: &Point, target_heading: f32, marker_down: bool) {
+        let reported = Point::new(self.current_x_coord, self.current_y_coord);
+        let report = StrokeReport {
+            target: *target,
+            reported,
+            marker_down,
+            position_error: calculate_distance(target, &reported),
+            heading_error: (self.current_heading - target_heading + 180.0).rem_euclid(360.0)
+                - 180.0,
+        };
+
+        if report.position_error >= 1.0 {
+            println!(
+                "Stroke to {},{} finished {}mm away",
+                target.x_coord, target.y_coord, report.position_error
+            );
+        }
+        self.stroke_reports.push(report);
+    }
+
+    // Have the robot navigate to an absolute position, optionally turning to a heading once there
+    async fn navigate_to(
+        &mut self,
+        robot: &RootRobot,
+        destination: &Point,
+        heading: Option<f32>,
+    ) -> Result<(), RootError> {
+        self.follow_session(robot);
+        let (x_coord, y_coord) = self.to_robot_position(destination);
+        let start = Point::new(self.current_x_coord, self.current_y_coord);
+        // Without a heading the robot ends up facing the way it drove, unless it had nowhere to go
+        let end_heading = heading.unwrap_or(if *destination == start {
+            self.current_heading
+        } else {
+            calculate_angle(&start, destination)
+        });
+        let response = robot
+            .navigate_to_position(
+                x_coord,
+                y_coord,
+                heading.map(|heading| to_robot_heading(heading - self.origin_heading)),
+            )
+            .await
+            .map_err(|err| self.motion_interrupted(err, *destination, end_heading))?;
+
+        self.reconcile(response.x_coord, response.y_coord, response.heading);
+        Ok(())
     }
 
     // Rotate the robot to an exact heading
-    async fn rotate_to_new_heading(&mut self, robot: &RootRobot, new_heading: f32) {
+    async fn rotate_to_new_heading(
+        &mut self,
+        robot: &RootRobot,
+        new_heading: f32,
+    ) -> Result<(), RootError> {
+        if self.use_navigation {
+            let destination = Point::new(self.current_x_coord, self.current_y_coord);
+            return self
+                .navigate_to(robot, &destination, Some(new_heading))
+                .await;
+        }
+
+        self.follow_session(robot);
         if new_heading != self.current_heading {
             let mut rotation_amount = new_heading - self.current_heading;
 
             // Sometimes we can shortcut rotation in the other direction
             if rotation_amount > 180.0 {
-                rotation_amount = rotation_amount - 360.0;
+                rotation_amount -= 360.0;
             } else if rotation_amount < -180.0 {
-                rotation_amount = rotation_amount + 360.0;
+                rotation_amount += 360.0;
             }
 
-            robot.rotate_angle((rotation_amount * 10.0) as i32).await;
-
-            self.current_heading = new_heading;
+            let position = Point::new(self.current_x_coord, self.current_y_coord);
+            let response = robot
+                .rotate_angle((rotation_amount * 10.0).round() as i32)
+                .await
+                .map_err(|err| self.motion_interrupted(err, position, new_heading))?;
+            self.reconcile(response.x_coord, response.y_coord, response.heading);
         }
+        Ok(())
     }
 
     // Move to a specified location
@@ -39,22 +277,47 @@ impl LinearOrchestrator {
         robot: &RootRobot,
         destination: &Point,
         marker_down: bool,
-    ) {
+    ) -> Result<(), RootError> {
         if destination.x_coord == self.current_x_coord
             && destination.y_coord == self.current_y_coord
         {
             // Already there no work needed
-            return;
+            return Ok(());
+        }
+
+        // Either way the robot ends up facing along the line it drove
+        let start = Point::new(self.current_x_coord, self.current_y_coord);
+        let target_heading = calculate_angle(&start, destination);
+
+        if self.use_navigation {
+            // The marker sits on the robot's center of rotation, so turning to face the destination draws nothing
+            if marker_down {
+                robot.set_marker_position(self.tool).await?;
+            }
+
+            println!(
+                "Navigating to {},{}",
+                destination.x_coord, destination.y_coord
+            );
+            let result = self.navigate_to(robot, destination, None).await;
+            if marker_down {
+                self.count_stroke(&result);
+            }
+            result?;
+
+            if marker_down {
+                robot
+                    .set_marker_position(MarkerPosition::NothingDown)
+                    .await?;
+            }
+            self.record_stroke(destination, target_heading, marker_down);
+            return Ok(());
         }
 
         //calculate how to move from current location to new location
-        let rotate_angle: f32 = calculate_angle(
-            &Point::new(self.current_x_coord,
-            self.current_y_coord),
-            destination,
-        );
-        self.rotate_to_new_heading(robot, rotate_angle).await;
+        self.rotate_to_new_heading(robot, target_heading).await?;
 
+        self.follow_session(robot);
         let distance = calculate_distance(
             &Point::new(self.current_x_coord, self.current_y_coord),
             destination,
@@ -62,19 +325,30 @@ impl LinearOrchestrator {
 
         if distance != 0.0 {
             if marker_down {
-                robot.set_marker_position(MarkerPosition::MarkerDown).await;
+                robot.set_marker_position(self.tool).await?;
             }
 
             println!("Driving forward {}", distance);
-            robot.drive_distance(distance.trunc() as i32).await;
+            let heading = self.current_heading;
+            let result = robot
+                .drive_distance(distance.round() as i32)
+                .await
+                .map_err(|err| self.motion_interrupted(err, *destination, heading));
+            if marker_down {
+                self.count_stroke(&result);
+            }
+            let response = result?;
+            self.reconcile(response.x_coord, response.y_coord, response.heading);
 
             if marker_down {
-                robot.set_marker_position(MarkerPosition::NothingDown).await;
+                robot
+                    .set_marker_position(MarkerPosition::NothingDown)
+                    .await?;
             }
         }
 
-        self.current_x_coord = destination.x_coord;
-        self.current_y_coord = destination.y_coord;
+        self.record_stroke(destination, target_heading, marker_down);
+        Ok(())
     }
 
     // To smooth out arcs we calculate an arc between current point, next point and the point after
@@ -87,17 +361,14 @@ impl LinearOrchestrator {
         mid: &Point,
         end: &Point,
         is_final: bool,
-    ) {
+    ) -> Result<(), RootError> {
         // calculate the center + radius
         let (center, radius) = calculate_radius_and_center(start, mid, end);
         let arc = calculate_degrees_of_rotation(start, mid, end, &center, is_final);
 
         // Rotate so we are facing perpindicular to center point (note that direction doesnt matter)
-        self.rotate_to_new_heading(
-            robot,
-            calculate_angle(start, &center) - 90.0,
-        )
-        .await;
+        self.rotate_to_new_heading(robot, calculate_angle(start, &center) - 90.0)
+            .await?;
 
         let destination = if is_final { end } else { mid };
 
@@ -106,51 +377,628 @@ impl LinearOrchestrator {
             center.x_coord, center.y_coord, radius, arc
         );
         // actually draw
-        robot.set_marker_position(MarkerPosition::MarkerDown).await;
-        robot.drive_arc(arc as i32 * 10, radius as i32).await;
-        robot.set_marker_position(MarkerPosition::NothingDown).await;
+        robot.set_marker_position(self.tool).await?;
+        self.follow_session(robot);
+        let heading = calculate_angle(destination, &center) - 90.0;
+        let result = robot
+            .drive_arc(arc as i32 * 10, radius as i32)
+            .await
+            .map_err(|err| self.motion_interrupted(err, *destination, heading));
+        self.count_stroke(&result);
+        let response = result?;
+        robot
+            .set_marker_position(MarkerPosition::NothingDown)
+            .await?;
 
-        // update the heading and coordinates
-        self.current_x_coord = destination.x_coord;
-        self.current_y_coord = destination.y_coord;
-        self.current_heading = calculate_angle( destination, &center) - 90.0;
+        // update the heading and coordinates from where the robot says it ended up
+        self.reconcile(response.x_coord, response.y_coord, response.heading);
+        self.record_stroke(
+            destination,
+            calculate_angle(destination, &center) - 90.0,
+            true,
+        );
+        Ok(())
     }
 
-    // Simple orchestrator which takes a set of lines (list of points) to draw
-    pub async fn orchestrate(&mut self, robot: &RootRobot, points: Vec<Vec<Point>>) {
+    // Draw a single line of the drawing, leaving out any strokes already drawn
+    async fn draw_line(&mut self, robot: &RootRobot, line: &[Point]) -> Result<(), RootError> {
+        if line.len() == 1 {
+            // if a vector has 1 point, draw a line straight to the point
+            if self.strokes_drawn == 0 {
+                self.move_straight_line(robot, &line[0], true).await?;
+            }
+        } else if line.len() == 2 {
+            // if a vector has 2 points, move to the first point, then draw a line to the second
+            if self.strokes_drawn == 0 {
+                self.move_straight_line(robot, &line[0], false).await?;
+                self.move_straight_line(robot, &line[1], true).await?;
+            }
+        } else if line.len() > 2 {
+            // if a vector has 3 or more points, move to the start of the first arc left to draw, then draw an
+            // arc between lines
+            let mut counter = self.strokes_drawn;
+            if counter + 3 <= line.len() {
+                self.move_straight_line(robot, &line[counter], false)
+                    .await?;
+            }
+
+            // Go through the arcs
+            while counter + 3 <= line.len() {
+                self.draw_arc(
+                    robot,
+                    line.get(counter).unwrap(),
+                    line.get(counter + 1).unwrap(),
+                    line.get(counter + 2).unwrap(),
+                    counter + 3 == line.len(),
+                )
+                .await?;
+
+                counter += 1;
+            }
+        } else {
+            // TODO: What should I do if I have more then 2, calculate best fit?
+        }
+        Ok(())
+    }
+
+    // Once a safety pause is over or the robot has reconnected, find out where the robot ended up and
+    // carry on with the interrupted line from the end of its last finished stroke, or from where the
+    // line started if none were finished
+    async fn resume_line(
+        &mut self,
+        robot: &RootRobot,
+        line_start: &Point,
+        line: &[Point],
+    ) -> Result<(), RootError> {
+        robot
+            .set_marker_position(MarkerPosition::NothingDown)
+            .await?;
+        self.follow_session(robot);
+        let position = robot.get_position().await?;
+        self.reconcile(position.x_coord, position.y_coord, position.heading);
+
+        if self.strokes_drawn == 0 {
+            self.move_straight_line(robot, line_start, false).await?;
+        }
+        self.draw_line(robot, line).await
+    }
+
+    // Refuse to start a drawing the battery probably can't finish, estimating use from how far the robot will travel
+    async fn check_battery(
+        &self,
+        robot: &RootRobot,
+        points: &[Vec<Point>],
+    ) -> Result<(), RootError> {
+        let start = Point::new(self.current_x_coord, self.current_y_coord);
+        let needed = path_length(&start, points) / 1000.0 * BATTERY_PERCENT_PER_METRE
+            + BATTERY_RESERVE_PERCENT;
+
+        let battery = robot.get_battery_level().await?;
+        if (battery.percent as f32) < needed {
+            return Err(RootError::InsufficientBattery {
+                available: battery.percent,
+                needed: needed.ceil() as u8,
+            });
+        }
+        Ok(())
+    }
+
+    // Simple orchestrator which takes a set of lines (list of points) to draw.
+    // If the robot pauses for safety or loses its connection part way through a line, the rest of the line
+    // is drawn once the pause is over or the robot is reconnected, starting with the stroke which was cut
+    // short. A safety stop or the robot being disconnected on purpose ends the drawing.
+    pub async fn orchestrate(
+        &mut self,
+        robot: &RootRobot,
+        points: Vec<Vec<Point>>,
+    ) -> Result<(), RootError> {
+        self.check_battery(robot, &points).await?;
+        if self.auto_gravity_compensation {
+            self.configure_for_surface(robot).await?;
+        }
+
+        self.session = robot.session();
         for line in points.iter() {
-            if line.len() == 1 {
-                // if a vector has 1 point, draw a line straight to the point
-                self.move_straight_line(robot, line.get(0).unwrap(), true)
-                    .await;
-            } else if line.len() == 2 {
-                // if a vector has 2 points, move to the first point, then draw a line to the second
-                self.move_straight_line(robot, line.get(0).unwrap(), false)
-                    .await;
-                self.move_straight_line(robot, line.get(1).unwrap(), true)
-                    .await;
-            } else if line.len() > 2 {
-                // if a vector has 3 or more points, move to the first point, then draw an arc between lines
-                self.move_straight_line(robot, line.get(0).unwrap(), false)
-                    .await;
-                let mut counter = 0;
-
-                // Go through the arcs
-                while counter + 3 <= line.len() {
-                    self.draw_arc(
-                        robot,
-                        line.get(counter).unwrap(),
-                        line.get(counter + 1).unwrap(),
-                        line.get(counter + 2).unwrap(),
-                        counter + 3 == line.len(),
-                    )
-                    .await;
-
-                    counter += 1;
+            let line_start = Point::new(self.current_x_coord, self.current_y_coord);
+            self.strokes_drawn = 0;
+
+            let mut result = self.draw_line(robot, line).await;
+            loop {
+                match result {
+                    Err(RootError::SafetyStop(reason)) => {
+                        println!("Line interrupted: {}", reason);
+                        robot.wait_for_resume().await?;
+                    }
+                    Err(RootError::Disconnected | RootError::ConnectionLost) => {
+                        println!("Line interrupted by losing the connection, reconnecting");
+                        robot.reconnect().await?;
+                    }
+                    _ => break,
+                }
+                result = self.resume_line(robot, &line_start, line).await;
+            }
+            result?;
+        }
+        Ok(())
+    }
+
+    // Retrace a drawing with the eraser down instead of the marker, wiping out what orchestrate drew
+    pub async fn erase(
+        &mut self,
+        robot: &RootRobot,
+        points: Vec<Vec<Point>>,
+    ) -> Result<(), RootError> {
+        self.tool = MarkerPosition::EraserDown;
+        let result = self.orchestrate(robot, points).await;
+        self.tool = MarkerPosition::MarkerDown;
+        result
+    }
+
+    // Sweep the eraser back and forth over the rectangle between two opposite corners
+    pub async fn erase_rectangle(
+        &mut self,
+        robot: &RootRobot,
+        corner: &Point,
+        opposite: &Point,
+    ) -> Result<(), RootError> {
+        self.erase(robot, sweep_path(corner, opposite)).await
+    }
+}
+
+// Total distance travelled drawing the lines from a starting point, counting arcs as straight lines
+// between their points
+fn path_length(start: &Point, points: &[Vec<Point>]) -> f32 {
+    let mut current = start;
+    let mut length = 0.0;
+    for point in points.iter().flatten() {
+        length += calculate_distance(current, point);
+        current = point;
+    }
+    length
+}
+
+// Back and forth passes along x covering a rectangle, stepping along y between them. The first pass is
+// travelled to with everything up, then the rest of the path is one continuous stroke.
+fn sweep_path(corner: &Point, opposite: &Point) -> Vec<Vec<Point>> {
+    let (left, right) = (
+        corner.x_coord.min(opposite.x_coord),
+        corner.x_coord.max(opposite.x_coord),
+    );
+    let (bottom, top) = (
+        corner.y_coord.min(opposite.y_coord),
+        corner.y_coord.max(opposite.y_coord),
+    );
+
+    let passes = ((top - bottom) / ERASER_SWEEP_SPACING_MM).ceil().max(0.0) as usize + 1;
+    let spacing = if passes > 1 {
+        (top - bottom) / (passes - 1) as f32
+    } else {
+        0.0
+    };
+
+    let mut path = vec![vec![Point::new(left, bottom), Point::new(right, bottom)]];
+    for pass in 1..passes {
+        let y_coord = bottom + spacing * pass as f32;
+        let (from, to) = if pass % 2 == 0 {
+            (left, right)
+        } else {
+            (right, left)
+        };
+        path.push(vec![Point::new(from, y_coord)]);
+        path.push(vec![Point::new(to, y_coord)]);
+    }
+    path
+}
+
+impl Default for LinearOrchestrator {
+    fn default() -> Self {
+        LinearOrchestrator::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irobot::root::transport::LoopbackTransport;
+    use crate::irobot::root::{
+        build_checked_packet, spawn_message_loop, DefaultSafetyPolicy, SafetyAction, SimulatedRoot,
+    };
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn can_orchestrate_against_simulator() {
+        let sim = SimulatedRoot::new();
+        let robot = sim.connect_running();
+
+        let mut orch = LinearOrchestrator::new();
+        orch.orchestrate(
+            &robot,
+            vec![vec![Point::new(0.0, 50.0), Point::new(50.0, 50.0)]],
+        )
+        .await
+        .unwrap();
+
+        let pose = sim.pose();
+        assert!((pose.x_coord - 50.0).abs() < 0.01);
+        assert!((pose.y_coord - 50.0).abs() < 0.01);
+        assert!(pose.heading.abs() < 0.01);
+        assert_eq!(sim.marker_position(), MarkerPosition::NothingDown as u8);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn can_orchestrate_with_navigation() {
+        let sim = SimulatedRoot::new();
+        let robot = sim.connect_running();
+
+        let mut orch = LinearOrchestrator::new();
+        orch.set_use_navigation(true);
+        orch.orchestrate(
+            &robot,
+            vec![
+                vec![Point::new(0.0, 50.0), Point::new(50.0, 50.0)],
+                vec![
+                    Point::new(50.0, 50.0),
+                    Point::new(75.0, 25.0),
+                    Point::new(50.0, 0.0),
+                ],
+            ],
+        )
+        .await
+        .unwrap();
+
+        let pose = sim.pose();
+        assert!((pose.x_coord - 50.0).abs() < 0.5);
+        assert!(pose.y_coord.abs() < 0.5);
+        assert_eq!(sim.marker_position(), MarkerPosition::NothingDown as u8);
+
+        // Travel to the start is pen up, the stroke and the arc are drawn
+        let trail = sim.trail();
+        assert_eq!(trail.len(), 2);
+        assert!(!trail[0].marker_down);
+        assert!(trail[1].marker_down);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn corrects_for_drift_between_strokes() {
+        let sim = SimulatedRoot::new();
+        let robot = sim.connect_running();
+
+        // Each stroke is a fraction of a mm off what the robot can drive, so the error would build up
+        // if every move assumed the last one landed exactly
+        let mut orch = LinearOrchestrator::new();
+        orch.orchestrate(
+            &robot,
+            (1..=5)
+                .map(|step| vec![Point::new(0.0, step as f32 * 10.4)])
+                .collect(),
+        )
+        .await
+        .unwrap();
+
+        let pose = sim.pose();
+        assert!(pose.x_coord.abs() < 0.01);
+        assert!((pose.y_coord - 52.0).abs() < 0.01);
+
+        let reports = orch.stroke_reports();
+        assert_eq!(reports.len(), 5);
+        for report in reports {
+            assert!(report.marker_down);
+            assert!(report.position_error < 0.5);
+            assert!(report.heading_error.abs() < 0.01);
+        }
+        assert_eq!(reports[4].reported, Point::new(0.0, 52.0));
+    }
+
+    #[test]
+    fn can_estimate_path_length() {
+        let points = vec![
+            vec![Point::new(0.0, 30.0), Point::new(40.0, 30.0)],
+            vec![Point::new(40.0, 0.0)],
+        ];
+        assert_eq!(path_length(&Point::new(0.0, 0.0), &points), 100.0);
+        assert_eq!(path_length(&Point::new(0.0, 0.0), &[]), 0.0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn refuses_to_start_on_low_battery() {
+        let sim = SimulatedRoot::new();
+        sim.set_battery_percent(10);
+        let robot = sim.connect_running();
+
+        let mut orch = LinearOrchestrator::new();
+        let result = orch
+            .orchestrate(
+                &robot,
+                vec![vec![Point::new(0.0, 50.0), Point::new(50.0, 50.0)]],
+            )
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(RootError::InsufficientBattery {
+                available: 10,
+                needed: 11
+            })
+        ));
+        assert!(sim.trail().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn sets_gravity_compensation_for_surface() {
+        let sim = SimulatedRoot::new();
+        let robot = sim.connect_running();
+
+        let mut orch = LinearOrchestrator::new();
+        orch.set_auto_gravity_compensation(true);
+
+        sim.set_accelerometer(0, -1000, 0);
+        orch.orchestrate(&robot, vec![vec![Point::new(0.0, 10.0)]])
+            .await
+            .unwrap();
+        assert!(sim.accelerometer_enabled());
+        assert_eq!(sim.gravity_compensation(), (0x01, 500));
+
+        sim.set_accelerometer(0, 0, 1000);
+        assert_eq!(
+            orch.configure_for_surface(&robot).await.unwrap(),
+            Orientation::Horizontal
+        );
+
+        // Setting gravity compensation has no response, so wait on something that does
+        robot.get_position().await.unwrap();
+        assert_eq!(sim.gravity_compensation(), (0x00, 500));
+    }
+
+    #[test]
+    fn can_plan_sweep_path() {
+        let path = sweep_path(&Point::new(40.0, 30.0), &Point::new(0.0, 0.0));
+
+        // 30mm needs passes at 0, 15 and 30
+        assert_eq!(
+            path,
+            vec![
+                vec![Point::new(0.0, 0.0), Point::new(40.0, 0.0)],
+                vec![Point::new(40.0, 15.0)],
+                vec![Point::new(0.0, 15.0)],
+                vec![Point::new(0.0, 30.0)],
+                vec![Point::new(40.0, 30.0)],
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn can_erase_a_drawing() {
+        let sim = SimulatedRoot::new();
+        let robot = sim.connect_running();
+
+        let drawing = vec![vec![Point::new(0.0, 50.0), Point::new(50.0, 50.0)]];
+        let mut orch = LinearOrchestrator::new();
+        orch.orchestrate(&robot, drawing.clone()).await.unwrap();
+        orch.erase(&robot, drawing).await.unwrap();
+
+        let trail = sim.trail();
+        let erased = trail
+            .iter()
+            .filter(|segment| segment.eraser_down)
+            .collect::<Vec<_>>();
+        assert_eq!(erased.len(), 1);
+        let start = erased[0].points[0];
+        let end = erased[0].points[1];
+        assert!(calculate_distance(&start, &Point::new(0.0, 50.0)) < 0.01);
+        assert!(calculate_distance(&end, &Point::new(50.0, 50.0)) < 0.01);
+        assert_eq!(sim.marker_position(), MarkerPosition::NothingDown as u8);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn resumes_line_after_safety_pause() {
+        let sim = SimulatedRoot::new();
+        let (transport, mut peer) = LoopbackTransport::pair();
+        let mut robot = RootRobot::new(transport);
+        robot.set_safety_policy(DefaultSafetyPolicy {
+            bumper: SafetyAction::BackOff(20),
+            ..Default::default()
+        });
+        let robot = spawn_message_loop(robot);
+
+        // Bump into something instead of carrying out the first drive with the marker down,
+        // then release the bumper once the robot has backed off
+        let peer_sim = sim.clone();
+        tokio::spawn(async move {
+            let mut bumped = false;
+            while let Some(packet) = peer.next_packet().await {
+                if !bumped && packet[0..2] == [0x01, 0x08] && peer_sim.marker_position() == 0x01 {
+                    bumped = true;
+                    peer.notify(build_checked_packet(vec![
+                        0x0C, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0A, 0x80,
+                    ]));
+                    continue;
+                }
+
+                let backing_off = bumped && packet[0..2] == [0x01, 0x08] && packet[3] == 0xFF;
+                for response in peer_sim.handle_packet(&packet) {
+                    peer.notify(response);
+                }
+
+                if backing_off {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    peer.notify(build_checked_packet(vec![
+                        0x0C, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0B, 0x00,
+                    ]));
                 }
-            } else {
-                // TODO: What should I do if I have more then 2, calculate best fit?
             }
+        });
+
+        let mut orch = LinearOrchestrator::new();
+        orch.orchestrate(
+            &robot,
+            vec![vec![Point::new(0.0, 50.0), Point::new(50.0, 50.0)]],
+        )
+        .await
+        .unwrap();
+
+        let pose = sim.pose();
+        assert!((pose.x_coord - 50.0).abs() < 0.5);
+        assert!((pose.y_coord - 50.0).abs() < 0.5);
+        assert_eq!(sim.marker_position(), MarkerPosition::NothingDown as u8);
+
+        // The stroke only happened once, after the pause
+        let inked = sim
+            .trail()
+            .iter()
+            .filter(|segment| segment.marker_down)
+            .count();
+        assert_eq!(inked, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn resumes_line_after_reconnecting() {
+        let sim = SimulatedRoot::new();
+        let robot = sim.connect_running();
+
+        // Battery check and the first line go through, then the link drops as the robot turns for the second
+        sim.drop_connection_after(4);
+        let mut orch = LinearOrchestrator::new();
+        orch.orchestrate(
+            &robot,
+            vec![
+                vec![Point::new(0.0, 0.0), Point::new(0.0, 100.0)],
+                vec![Point::new(100.0, 100.0)],
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(robot.session(), 1);
+
+        // The robot still finished the turn, then started counting again from (0, 0) facing its +y, which
+        // is already along the second line
+        let trail = sim.trail();
+        let last = trail
+            .iter()
+            .rev()
+            .find(|segment| segment.marker_down)
+            .unwrap();
+        let end = last.points.last().unwrap();
+        assert!(end.x_coord.abs() < 0.5);
+        assert!((end.y_coord - 100.0).abs() < 0.5);
+
+        let report = orch.stroke_reports().last().unwrap();
+        assert_eq!(report.target, Point::new(100.0, 100.0));
+        assert!(report.position_error < 0.5);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn resumes_line_after_link_drops_mid_drive() {
+        let sim = SimulatedRoot::new();
+        let robot = sim.connect_running();
+
+        // The link drops as the first line is being drawn, after the robot has been told to drive it
+        sim.drop_connection_after(2);
+        let mut orch = LinearOrchestrator::new();
+        orch.orchestrate(
+            &robot,
+            vec![
+                vec![Point::new(0.0, 0.0), Point::new(0.0, 100.0)],
+                vec![Point::new(100.0, 100.0)],
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(robot.session(), 1);
+
+        // The robot finished the first line before reconnecting at its end, so that line is inked once and
+        // the second goes off to its right from there
+        let trail = sim.trail();
+        let inked: Vec<_> = trail.iter().filter(|segment| segment.marker_down).collect();
+        assert_eq!(inked.len(), 2);
+        let expected = [(0.0, 0.0), (100.0, 0.0)];
+        assert_eq!(inked[1].points.len(), expected.len());
+        for (point, (x_coord, y_coord)) in inked[1].points.iter().zip(expected) {
+            assert!((point.x_coord - x_coord).abs() < 0.5);
+            assert!((point.y_coord - y_coord).abs() < 0.5);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn rebases_from_confirmed_pose_when_link_drops_between_lines() {
+        let sim = SimulatedRoot::new();
+        let robot = sim.connect_running();
+
+        // The first line is finished and answered, then the link drops before the turn for the second
+        // can reach the robot
+        sim.drop_connection_after_answering(4);
+        let mut orch = LinearOrchestrator::new();
+        orch.orchestrate(
+            &robot,
+            vec![
+                vec![Point::new(0.0, 0.0), Point::new(0.0, 100.0)],
+                vec![Point::new(100.0, 100.0)],
+                vec![Point::new(100.0, 0.0)],
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(robot.session(), 1);
+
+        // The robot restarted from (0, 0) at the end of the first line facing its +y, so it had to turn
+        // right for the second line and then turn again for the third
+        let trail = sim.trail();
+        let inked: Vec<_> = trail.iter().filter(|segment| segment.marker_down).collect();
+        assert_eq!(inked.len(), 2);
+        let expected = [(0.0, 0.0), (100.0, 0.0), (100.0, -100.0)];
+        assert_eq!(inked[1].points.len(), expected.len());
+        for (point, (x_coord, y_coord)) in inked[1].points.iter().zip(expected) {
+            assert!((point.x_coord - x_coord).abs() < 0.5);
+            assert!((point.y_coord - y_coord).abs() < 0.5);
         }
     }
-}
\ No newline at end of file
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn stops_drawing_when_disconnected_on_purpose() {
+        let (transport, mut peer) = LoopbackTransport::pair();
+        let robot = Arc::new(RootRobot::new(transport));
+
+        let loop_robot = robot.clone();
+        let message_loop = tokio::spawn(async move { loop_robot.run_message_loop().await });
+
+        // Answer the battery check, then disconnect instead of answering the marker
+        let disconnect_robot = robot.clone();
+        tokio::spawn(async move {
+            let battery = peer.next_packet().await.unwrap();
+            peer.notify(build_checked_packet(vec![
+                0x0E, 0x01, battery[2], 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 100,
+            ]));
+            peer.next_packet().await.unwrap();
+            disconnect_robot.disconnect().await.unwrap();
+            peer
+        });
+
+        let mut orch = LinearOrchestrator::new();
+        let result = orch
+            .orchestrate(
+                &robot,
+                vec![vec![Point::new(0.0, 0.0), Point::new(0.0, 100.0)]],
+            )
+            .await;
+
+        assert!(matches!(result, Err(RootError::Disconnected)));
+        assert_eq!(robot.session(), 0);
+        assert!(message_loop.await.unwrap().is_ok());
+    }
+
+    #[test]
+    fn maps_positions_through_origin() {
+        let mut orch = LinearOrchestrator::new();
+        orch.current_x_coord = 100.0;
+        orch.current_y_coord = 50.0;
+        orch.current_heading = 90.0;
+        orch.rebase_origin();
+
+        // Straight ahead of the robot is along the drawing's +x
+        assert_eq!(orch.to_robot_position(&Point::new(130.0, 50.0)), (0, 30));
+        orch.reconcile(0, 30, 900);
+        assert!((orch.current_x_coord - 130.0).abs() < 0.01);
+        assert!((orch.current_y_coord - 50.0).abs() < 0.01);
+        assert!((orch.current_heading - 90.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,207 @@
+use std::time::Duration;
+
+use futures::stream::StreamExt;
+use tokio::time::Instant;
+
+use crate::irobot::root::{
+    messages::{Color, ColorSensorEvent, COLOR_SENSOR_COUNT},
+    RootError, RootRobot, MAX_MOTOR_SPEED_MM_PER_SEC,
+};
+
+// Why following the line stopped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineFollowEnd {
+    LineLost,
+    TimeUp,
+}
+
+// Steers along a drawn line by watching which color sensors see it and driving the two motors at
+// different speeds to keep it centered under the robot
+pub struct LineFollower {
+    line_color: Color,
+    speed: f32,         // in mm/s while the line is centered
+    steering_gain: f32, // extra mm/s given to the outside wheel per sensor the line is off center
+}
+
+impl LineFollower {
+    pub fn new() -> LineFollower {
+        LineFollower {
+            line_color: Color::Black,
+            speed: 50.0,
+            steering_gain: 4.0,
+        }
+    }
+
+    pub fn set_line_color(&mut self, line_color: Color) {
+        self.line_color = line_color;
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    pub fn set_steering_gain(&mut self, steering_gain: f32) {
+        self.steering_gain = steering_gain;
+    }
+
+    // Sensors the line is right of center, negative when it is to the left, or None if no sensor can see it
+    fn line_offset(&self, event: &ColorSensorEvent) -> Option<f32> {
+        let (count, total) = event
+            .colors
+            .iter()
+            .enumerate()
+            .filter(|(_, color)| **color == self.line_color)
+            .fold((0, 0), |(count, total), (index, _)| {
+                (count + 1, total + index)
+            });
+
+        if count == 0 {
+            return None;
+        }
+        Some(total as f32 / count as f32 - (COLOR_SENSOR_COUNT - 1) as f32 / 2.0)
+    }
+
+    // Left and right motor speeds which turn towards the line
+    fn motor_speeds(&self, offset: f32) -> (i32, i32) {
+        let steer = |speed: f32| {
+            let max_speed = MAX_MOTOR_SPEED_MM_PER_SEC as f32;
+            speed.clamp(-max_speed, max_speed).round() as i32
+        };
+        (
+            steer(self.speed + self.steering_gain * offset),
+            steer(self.speed - self.steering_gain * offset),
+        )
+    }
+
+    // Follow the line until it is lost or the time runs out, then stop. The robot starts by driving
+    // straight ahead, so it should be placed over the line facing along it.
+    pub async fn follow(
+        &self,
+        robot: &RootRobot,
+        duration: Duration,
+    ) -> Result<LineFollowEnd, RootError> {
+        let mut events = Box::pin(robot.color_sensor_events());
+        let deadline = Instant::now() + duration;
+
+        let result = async {
+            let mut speeds = self.motor_speeds(0.0);
+            robot.set_motor_speeds(speeds.0, speeds.1).await?;
+
+            loop {
+                let event = match tokio::time::timeout_at(deadline, events.next()).await {
+                    Ok(Some(event)) => event,
+                    Ok(None) => return Err(RootError::Disconnected),
+                    Err(_) => return Ok(LineFollowEnd::TimeUp),
+                };
+
+                let offset = match self.line_offset(&event) {
+                    Some(offset) => offset,
+                    None => return Ok(LineFollowEnd::LineLost),
+                };
+
+                // The robot only sends events when the colors change, so there is nothing to do if the
+                // speeds come out the same
+                let new_speeds = self.motor_speeds(offset);
+                if new_speeds != speeds {
+                    speeds = new_speeds;
+                    robot.set_motor_speeds(speeds.0, speeds.1).await?;
+                }
+            }
+        }
+        .await;
+
+        // Stop however we finished, but report the original error over any from stopping
+        let stopped = robot.set_motor_speeds(0, 0).await;
+        let end = result?;
+        stopped?;
+        Ok(end)
+    }
+}
+
+impl Default for LineFollower {
+    fn default() -> Self {
+        LineFollower::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irobot::root::transport::LoopbackTransport;
+    use crate::irobot::root::{build_checked_packet, spawn_message_loop, Message};
+
+    fn color_event(line_sensors: std::ops::Range<usize>) -> Vec<u8> {
+        let mut colors = [0u8; COLOR_SENSOR_COUNT];
+        for sensor in line_sensors {
+            colors[sensor] = 0x01;
+        }
+
+        let mut packet = vec![0x04, 0x02, 0x00];
+        packet.extend(colors.chunks(2).map(|pair| pair[0] << 4 | pair[1]));
+        packet
+    }
+
+    fn motor_speeds(packet: &[u8]) -> (i32, i32) {
+        assert_eq!(&packet[0..2], &[0x01, 0x04]);
+        (
+            i32::from_be_bytes(packet[3..7].try_into().unwrap()),
+            i32::from_be_bytes(packet[7..11].try_into().unwrap()),
+        )
+    }
+
+    #[test]
+    fn can_find_line_offset() {
+        let follower = LineFollower::new();
+        let event = |sensors| {
+            ColorSensorEvent::new(Message {
+                data: color_event(sensors),
+            })
+            .unwrap()
+        };
+
+        assert_eq!(follower.line_offset(&event(15..17)), Some(0.0));
+        assert_eq!(follower.line_offset(&event(28..32)), Some(14.0));
+        assert_eq!(follower.line_offset(&event(0..2)), Some(-15.0));
+        assert_eq!(follower.line_offset(&event(0..0)), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn steers_towards_line_until_lost() {
+        let (transport, mut peer) = LoopbackTransport::pair();
+        let robot = spawn_message_loop(RootRobot::new(transport));
+
+        let driver = tokio::spawn(async move {
+            assert_eq!(motor_speeds(&peer.next_packet().await.unwrap()), (50, 50));
+
+            // Line off to the right, so the left wheel speeds up to turn towards it
+            peer.notify(build_checked_packet(color_event(20..24)));
+            assert_eq!(motor_speeds(&peer.next_packet().await.unwrap()), (74, 26));
+
+            peer.notify(build_checked_packet(color_event(0..0)));
+            assert_eq!(motor_speeds(&peer.next_packet().await.unwrap()), (0, 0));
+            peer
+        });
+
+        let end = LineFollower::new()
+            .follow(&robot, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(end, LineFollowEnd::LineLost);
+        driver.await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn stops_when_time_is_up() {
+        let (transport, mut peer) = LoopbackTransport::pair();
+        let robot = RootRobot::new(transport);
+
+        let end = LineFollower::new()
+            .follow(&robot, Duration::from_millis(20))
+            .await
+            .unwrap();
+        assert_eq!(end, LineFollowEnd::TimeUp);
+
+        assert_eq!(motor_speeds(&peer.next_packet().await.unwrap()), (50, 50));
+        assert_eq!(motor_speeds(&peer.next_packet().await.unwrap()), (0, 0));
+    }
+}
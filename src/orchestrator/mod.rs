@@ -1,3 +1,26 @@
 mod linearorchestrator;
 
 pub use self::linearorchestrator::LinearOrchestrator;
+pub use self::linearorchestrator::StrokeReport;
+
+mod svgrenderer;
+pub use self::svgrenderer::dry_run_svg;
+pub use self::svgrenderer::render_svg;
+
+mod linefollower;
+pub use self::linefollower::LineFollowEnd;
+pub use self::linefollower::LineFollower;
+
+mod lightanimator;
+pub use self::lightanimator::LightAnimation;
+pub use self::lightanimator::LightAnimator;
+pub use self::lightanimator::LightFrame;
+
+mod rootfleet;
+pub use self::rootfleet::DrawingJob;
+pub use self::rootfleet::RootFleet;
+
+mod collaborativeplanner;
+pub use self::collaborativeplanner::CollaborativePlan;
+pub use self::collaborativeplanner::RobotShare;
+pub use self::collaborativeplanner::RobotStart;
@@ -0,0 +1,297 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::join_all;
+use tokio::task::JoinHandle;
+
+use super::LinearOrchestrator;
+use crate::irobot::root::{LEDLightsState, RootError, RootRobot};
+use crate::utils::{discover_roots, DiscoveryFilter, Point};
+
+// A drawing for one robot in the fleet. The orchestrator is handed back once the drawing is done so its
+// stroke reports can be looked at.
+pub struct DrawingJob {
+    pub robot: String,
+    pub orchestrator: LinearOrchestrator,
+    pub points: Vec<Vec<Point>>,
+}
+
+impl DrawingJob {
+    pub fn new(robot: impl Into<String>, points: Vec<Vec<Point>>) -> DrawingJob {
+        DrawingJob {
+            robot: robot.into(),
+            orchestrator: LinearOrchestrator::new(),
+            points,
+        }
+    }
+}
+
+// Several connected robots addressed by name, each with its own message loop running in the background
+pub struct RootFleet {
+    robots: Vec<(String, Arc<RootRobot>)>,
+    message_loops: Vec<JoinHandle<()>>,
+}
+
+impl RootFleet {
+    pub fn new() -> RootFleet {
+        RootFleet {
+            robots: vec![],
+            message_loops: vec![],
+        }
+    }
+
+    // Connect to every Root matching the filter seen within the scan time. Robots are named by what they
+    // advertise, falling back on their address when that is missing or already taken.
+    pub async fn discover(
+        filter: &DiscoveryFilter,
+        scan_time: Duration,
+    ) -> Result<RootFleet, RootError> {
+        let mut fleet = RootFleet::new();
+        for candidate in discover_roots(filter, scan_time).await? {
+            let address = candidate.address.to_string();
+            let name = match candidate.name.clone() {
+                Some(name) if fleet.robot(&name).is_none() => name,
+                _ => address,
+            };
+
+            // One robot failing to connect shouldn't stop the rest of the class
+            let added = match candidate.connect().await {
+                Ok(robot) => fleet.add(name.clone(), robot).await.map(|_| ()),
+                Err(err) => Err(err),
+            };
+            if let Err(err) = added {
+                eprintln!("Skipping {}: {}", name, err);
+            }
+        }
+
+        if fleet.is_empty() {
+            return Err(RootError::NotFound);
+        }
+        Ok(fleet)
+    }
+
+    // Add a connected robot under a name, subscribing to it and starting its message loop.
+    // Must be called from within a tokio runtime as the message loop runs as a task.
+    pub async fn add(
+        &mut self,
+        name: impl Into<String>,
+        robot: RootRobot,
+    ) -> Result<Arc<RootRobot>, RootError> {
+        let name = name.into();
+        if self.robot(&name).is_some() {
+            return Err(RootError::InvalidArgument(format!(
+                "There is already a robot named {:?}",
+                name
+            )));
+        }
+
+        if let Err(err) = robot.subscribe().await {
+            // Let go of the robot rather than leave it connected with nobody listening to it
+            let _ = robot.disconnect().await;
+            return Err(err);
+        }
+        let robot = Arc::new(robot);
+        let loop_robot = robot.clone();
+        let loop_name = name.clone();
+        self.message_loops.push(tokio::spawn(async move {
+            if let Err(err) = loop_robot.run_message_loop().await {
+                eprintln!("Message loop for {} stopped: {}", loop_name, err);
+            }
+        }));
+        self.robots.push((name, robot.clone()));
+        Ok(robot)
+    }
+
+    pub fn robot(&self, name: &str) -> Option<Arc<RootRobot>> {
+        self.robots
+            .iter()
+            .find(|(robot_name, _)| robot_name == name)
+            .map(|(_, robot)| robot.clone())
+    }
+
+    // Names of every robot, in the order they were added
+    pub fn names(&self) -> Vec<&str> {
+        self.robots.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.robots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.robots.is_empty()
+    }
+
+    // Run the same command on every robot at once, giving back how it went for each by name
+    pub async fn broadcast<F, Fut, T>(&self, command: F) -> Vec<(String, Result<T, RootError>)>
+    where
+        F: Fn(Arc<RootRobot>) -> Fut,
+        Fut: Future<Output = Result<T, RootError>>,
+    {
+        let results = join_all(self.robots.iter().map(|(_, robot)| command(robot.clone()))).await;
+        self.robots
+            .iter()
+            .map(|(name, _)| name.clone())
+            .zip(results)
+            .collect()
+    }
+
+    pub async fn stop_all(&self) -> Vec<(String, Result<(), RootError>)> {
+        self.broadcast(|robot| async move { robot.stop_and_reset().await })
+            .await
+    }
+
+    // Leave every robot stopped with its marker up and disconnect from it, see RootRobot::shutdown
+    pub async fn shutdown_all(&self) -> Vec<(String, Result<(), RootError>)> {
+        self.broadcast(|robot| async move { robot.shutdown().await })
+            .await
+    }
+
+    pub async fn set_all_lights(
+        &self,
+        lights_state: LEDLightsState,
+        r: u8,
+        g: u8,
+        b: u8,
+    ) -> Vec<(String, Result<(), RootError>)> {
+        self.broadcast(|robot| async move { robot.set_lights(lights_state, r, g, b).await })
+            .await
+    }
+
+    // Draw each job on its own robot at the same time, each on its own task. Every job is checked for a
+    // robot before any start, then they are all handed back with how they went once the last one is done.
+    pub async fn draw(
+        &self,
+        jobs: Vec<DrawingJob>,
+    ) -> Result<Vec<(DrawingJob, Result<(), RootError>)>, RootError> {
+        let mut robots = vec![];
+        for job in jobs.iter() {
+            let robot = self.robot(&job.robot).ok_or_else(|| {
+                RootError::InvalidArgument(format!("No robot named {:?}", job.robot))
+            })?;
+            robots.push(robot);
+        }
+
+        let tasks: Vec<_> = jobs
+            .into_iter()
+            .zip(robots)
+            .map(|(mut job, robot)| {
+                tokio::spawn(async move {
+                    let result = job
+                        .orchestrator
+                        .orchestrate(&robot, job.points.clone())
+                        .await;
+                    (job, result)
+                })
+            })
+            .collect();
+
+        let mut results = vec![];
+        for task in tasks {
+            // The tasks are never cancelled, so the only way one can fail is by panicking
+            match task.await {
+                Ok(result) => results.push(result),
+                Err(err) => std::panic::resume_unwind(err.into_panic()),
+            }
+        }
+        Ok(results)
+    }
+}
+
+impl Default for RootFleet {
+    fn default() -> Self {
+        RootFleet::new()
+    }
+}
+
+impl Drop for RootFleet {
+    fn drop(&mut self) {
+        for message_loop in self.message_loops.iter() {
+            message_loop.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irobot::root::SimulatedRoot;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn draws_different_jobs_on_each_robot() {
+        let (left, right) = (SimulatedRoot::new(), SimulatedRoot::new());
+        let mut fleet = RootFleet::new();
+        fleet.add("left", left.connect()).await.unwrap();
+        fleet.add("right", right.connect()).await.unwrap();
+        assert_eq!(fleet.names(), vec!["left", "right"]);
+        assert!(matches!(
+            fleet.add("left", SimulatedRoot::new().connect()).await,
+            Err(RootError::InvalidArgument(_))
+        ));
+
+        let results = fleet
+            .draw(vec![
+                DrawingJob::new(
+                    "left",
+                    vec![vec![Point::new(0.0, 0.0), Point::new(0.0, 50.0)]],
+                ),
+                DrawingJob::new(
+                    "right",
+                    vec![vec![Point::new(0.0, 0.0), Point::new(30.0, 0.0)]],
+                ),
+            ])
+            .await
+            .unwrap();
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+        assert_eq!(results[1].0.robot, "right");
+
+        let drawn_to = |sim: &SimulatedRoot| {
+            let trail = sim.trail();
+            let drawn = trail.iter().find(|segment| segment.marker_down).unwrap();
+            *drawn.points.last().unwrap()
+        };
+        let left_end = drawn_to(&left);
+        let right_end = drawn_to(&right);
+        assert!((left_end.y_coord - 50.0).abs() < 0.01);
+        assert!((right_end.x_coord - 30.0).abs() < 0.01);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn broadcasts_to_every_robot() {
+        let sims = [SimulatedRoot::new(), SimulatedRoot::new()];
+        let mut fleet = RootFleet::new();
+        for (index, sim) in sims.iter().enumerate() {
+            fleet
+                .add(format!("root {}", index), sim.connect())
+                .await
+                .unwrap();
+        }
+
+        let results = fleet
+            .set_all_lights(LEDLightsState::On, 0xFF, 0x00, 0x00)
+            .await;
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+
+        // Lights don't respond, so ask for something that does to be sure they have been set
+        let names = fleet
+            .broadcast(|robot| async move { robot.get_name().await })
+            .await;
+        assert!(names.iter().all(|(_, name)| name.is_ok()));
+        for sim in sims.iter() {
+            assert_eq!(sim.lights(), (0x01, 0xFF, 0x00, 0x00));
+        }
+
+        assert!(matches!(
+            fleet.draw(vec![DrawingJob::new("missing", vec![])]).await,
+            Err(RootError::InvalidArgument(_))
+        ));
+
+        let results = fleet.shutdown_all().await;
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+        for sim in sims.iter() {
+            assert_eq!(sim.marker_position(), 0x00);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt::Write;
+
+use super::LinearOrchestrator;
+use crate::irobot::root::{RootError, SimulatedRoot, TrailSegment};
+use crate::utils::Point;
+
+// Space left around the drawing so strokes at the edge aren't clipped, in mm
+const SVG_MARGIN: f32 = 10.0;
+
+// Roughly the width of a whiteboard marker line, in mm
+const INK_STROKE_WIDTH: f32 = 2.0;
+const TRAVEL_STROKE_WIDTH: f32 = 0.5;
+
+// The eraser pad is much wider than the marker, so erasing is drawn as a broad white stroke over the ink
+const ERASER_STROKE_WIDTH: f32 = 20.0;
+
+// Render a marker trail as an SVG document, with ink as solid lines, erasing painted over in white and
+// pen-up travel dotted.
+// Coordinates are in mm, with y flipped so the picture matches the whiteboard.
+pub fn render_svg(trail: &[TrailSegment]) -> String {
+    let all_points = trail.iter().flat_map(|segment| segment.points.iter());
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (0.0_f32, 0.0_f32, 0.0_f32, 0.0_f32);
+    for point in all_points {
+        min_x = min_x.min(point.x_coord);
+        min_y = min_y.min(point.y_coord);
+        max_x = max_x.max(point.x_coord);
+        max_y = max_y.max(point.y_coord);
+    }
+
+    let width = max_x - min_x + 2.0 * SVG_MARGIN;
+    let height = max_y - min_y + 2.0 * SVG_MARGIN;
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}mm\" height=\"{}mm\" viewBox=\"{} {} {} {}\">",
+        width,
+        height,
+        min_x - SVG_MARGIN,
+        -max_y - SVG_MARGIN,
+        width,
+        height
+    )
+    .unwrap();
+
+    for segment in trail {
+        let points = segment
+            .points
+            .iter()
+            .map(|point| format!("{},{}", point.x_coord, 0.0 - point.y_coord))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        if segment.marker_down {
+            writeln!(
+                svg,
+                "  <polyline points=\"{}\" fill=\"none\" stroke=\"black\" stroke-width=\"{}\" stroke-linecap=\"round\" stroke-linejoin=\"round\"/>",
+                points, INK_STROKE_WIDTH
+            )
+            .unwrap();
+        } else if segment.eraser_down {
+            writeln!(
+                svg,
+                "  <polyline points=\"{}\" fill=\"none\" stroke=\"white\" stroke-width=\"{}\" stroke-linecap=\"round\" stroke-linejoin=\"round\"/>",
+                points, ERASER_STROKE_WIDTH
+            )
+            .unwrap();
+        } else {
+            writeln!(
+                svg,
+                "  <polyline points=\"{}\" fill=\"none\" stroke=\"grey\" stroke-width=\"{}\" stroke-dasharray=\"2,2\"/>",
+                points, TRAVEL_STROKE_WIDTH
+            )
+            .unwrap();
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Run a drawing against a simulated robot and render what it would have drawn.
+pub async fn dry_run_svg(points: Vec<Vec<Point>>) -> Result<String, RootError> {
+    let sim = SimulatedRoot::new();
+    let robot = sim.connect_running();
+
+    let mut orch = LinearOrchestrator::new();
+    orch.orchestrate(&robot, points).await?;
+
+    Ok(render_svg(&sim.trail()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_render_ink_erasing_and_travel() {
+        let svg = render_svg(&[
+            TrailSegment {
+                points: vec![Point::new(0.0, 0.0), Point::new(0.0, 20.0)],
+                marker_down: false,
+                eraser_down: false,
+            },
+            TrailSegment {
+                points: vec![Point::new(0.0, 20.0), Point::new(30.0, 20.0)],
+                marker_down: true,
+                eraser_down: false,
+            },
+            TrailSegment {
+                points: vec![Point::new(30.0, 20.0), Point::new(0.0, 20.0)],
+                marker_down: false,
+                eraser_down: true,
+            },
+        ]);
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains("viewBox=\"-10 -30 50 40\""));
+        assert!(svg.contains("points=\"0,0 0,-20\" fill=\"none\" stroke=\"grey\""));
+        assert!(svg.contains("points=\"0,-20 30,-20\" fill=\"none\" stroke=\"black\""));
+        assert!(svg.contains("points=\"30,-20 0,-20\" fill=\"none\" stroke=\"white\""));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn can_dry_run_drawing() {
+        let svg = dry_run_svg(vec![vec![Point::new(0.0, 50.0), Point::new(50.0, 50.0)]])
+            .await
+            .unwrap();
+
+        assert_eq!(svg.matches("stroke=\"black\"").count(), 1);
+        assert_eq!(svg.matches("stroke=\"grey\"").count(), 1);
+    }
+}
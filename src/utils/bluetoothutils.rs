@@ -1,73 +1,218 @@
-use crate::irobot::root::{is_root_robot, RootRobot};
+use crate::irobot::root::transport::BtleplugTransport;
+use crate::irobot::root::{
+    is_root_advertisement, is_root_robot, RootError, RootRobot, ROOT_IDENTIFIER_UUID,
+};
 
-use btleplug::api::{Central, Manager as _, Peripheral as _, ScanFilter};
-use btleplug::platform::Manager;
+use btleplug::api::{BDAddr, Central, CentralEvent, Manager as _, Peripheral as _, ScanFilter};
+use btleplug::platform::{Manager, Peripheral};
+use futures::stream::{self, StreamExt};
 use std::time::Duration;
-use tokio::time;
-
-// Helper which uses the btleplug library to scan available peripherals looking for a specific device.
-pub async fn find_root_peripheral() -> Option<RootRobot> {
-    let manager = Manager::new()
-        .await
-        .expect("Failed to load bluetooth manager");
-    let adapter_list = manager.adapters().await.expect("Failed to load adapters");
-    if adapter_list.is_empty() {
-        eprintln!("No Bluetooth adapters found");
+use tokio::time::{self, Instant};
+
+// How long find_root_peripheral scans for before giving up
+const DEFAULT_SCAN_TIME: Duration = Duration::from_secs(10);
+
+// Narrows discovery down to particular robots, anything left as None matches every robot
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiscoveryFilter {
+    pub name: Option<String>,
+    pub address: Option<BDAddr>,
+    // Weakest signal to accept in dBm, robots which have not reported a signal strength never match
+    pub min_rssi: Option<i16>,
+}
+
+impl DiscoveryFilter {
+    pub fn matches(&self, name: Option<&str>, address: BDAddr, rssi: Option<i16>) -> bool {
+        self.name
+            .as_deref()
+            .is_none_or(|wanted| name == Some(wanted))
+            && self.address.is_none_or(|wanted| address == wanted)
+            && self
+                .min_rssi
+                .is_none_or(|min_rssi| rssi.is_some_and(|rssi| rssi >= min_rssi))
     }
+}
 
-    for adapter in adapter_list.iter() {
-        println!(
-            "Starting scan on {}...",
-            adapter
-                .adapter_info()
-                .await
-                .expect("Failed to load adapter_info")
-        );
+// A Root seen advertising nearby, found from its advertisement without connecting to it
+#[derive(Clone, Debug)]
+pub struct RootCandidate {
+    pub name: Option<String>,
+    pub address: BDAddr,
+    pub rssi: Option<i16>,
+    peripheral: Peripheral,
+}
+
+impl RootCandidate {
+    // Connect to the robot, checking it really does have the Root service once connected
+    pub async fn connect(self) -> Result<RootRobot, RootError> {
+        if !self.peripheral.is_connected().await? {
+            println!("Connecting to {}...", self);
+            self.peripheral.connect().await?;
+        }
+        self.peripheral.discover_services().await?;
+
+        if !is_root_robot(&self.peripheral) {
+            self.peripheral.disconnect().await?;
+            return Err(RootError::NotFound);
+        }
+        Ok(RootRobot::new(BtleplugTransport::new(self.peripheral)))
+    }
+}
+
+impl std::fmt::Display for RootCandidate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({})",
+            self.name.as_deref().unwrap_or("(name unknown)"),
+            self.address
+        )
+    }
+}
+
+// Scan every adapter for Roots matching the filter, returning everything seen within the scan time with the
+// strongest signal first
+pub async fn discover_roots(
+    filter: &DiscoveryFilter,
+    scan_time: Duration,
+) -> Result<Vec<RootCandidate>, RootError> {
+    let mut candidates = scan(filter, scan_time, false).await?;
+    candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.rssi));
+    Ok(candidates)
+}
+
+// Scan for the first Root matching the filter and connect to it, stopping the scan as soon as one is seen
+pub async fn find_root(
+    filter: &DiscoveryFilter,
+    scan_time: Duration,
+) -> Result<RootRobot, RootError> {
+    match scan(filter, scan_time, true).await?.into_iter().next() {
+        Some(candidate) => candidate.connect().await,
+        None => Err(RootError::NotFound),
+    }
+}
 
+// Connect to whichever Root is seen first.
+pub async fn find_root_peripheral() -> Result<RootRobot, RootError> {
+    find_root(&DiscoveryFilter::default(), DEFAULT_SCAN_TIME).await
+}
+
+// Listen to advertisements on every adapter until the scan time is up, or the first match if that is all that
+// is wanted. Robots are picked out by their advertised service so nothing gets connected to while scanning.
+async fn scan(
+    filter: &DiscoveryFilter,
+    scan_time: Duration,
+    first_only: bool,
+) -> Result<Vec<RootCandidate>, RootError> {
+    let manager = Manager::new().await?;
+    let adapters = manager.adapters().await?;
+    if adapters.is_empty() {
+        eprintln!("No Bluetooth adapters found");
+    }
+
+    let mut adapter_events = vec![];
+    for adapter in adapters.iter() {
+        println!("Starting scan on {}...", adapter.adapter_info().await?);
+        let events = adapter.events().await?;
         adapter
-            .start_scan(ScanFilter::default())
-            .await
-            .expect("Can't scan BLE adapter for connected devices...");
-        time::sleep(Duration::from_secs(10)).await;
-        let peripherals = adapter.peripherals().await.expect("No peripherals found");
-
-        if peripherals.is_empty() {
-            eprintln!("->>> BLE peripheral devices were not found, sorry. Exiting...");
-        } else {
-            for peripheral in adapter.peripherals().await.unwrap() {
-                let properties = peripheral.properties().await.expect("No properties");
-                let is_connected = peripheral.is_connected().await.expect("Unknown connection");
-                let local_name = properties
-                    .unwrap()
-                    .local_name
-                    .unwrap_or(String::from("(peripheral name unknown)"));
-                println!(
-                    "Peripheral {:?} is connected: {:?}",
-                    local_name, is_connected
-                );
-                if !is_connected {
-                    println!("Connecting to peripheral {:?}...", &local_name);
-                    if let Err(err) = peripheral.connect().await {
-                        eprintln!("Error connecting to peripheral, skipping: {}", err);
-                        continue;
-                    }
-                }
-                let is_connected = peripheral.is_connected().await.expect("is _connected?");
-                println!(
-                    "Now connected ({:?}) to peripheral {:?}...",
-                    is_connected, &local_name
-                );
-                peripheral
-                    .discover_services()
-                    .await
-                    .expect("Failed to discover services");
-
-                if is_root_robot(&peripheral) {
-                    return Some(RootRobot::new(peripheral));
+            .start_scan(ScanFilter {
+                services: vec![ROOT_IDENTIFIER_UUID],
+            })
+            .await?;
+        let adapter = adapter.clone();
+        adapter_events.push(events.map(move |event| (adapter.clone(), event)));
+    }
+    let mut events = stream::select_all(adapter_events);
+
+    let deadline = Instant::now() + scan_time;
+    let mut candidates: Vec<RootCandidate> = vec![];
+    while let Ok(Some((adapter, event))) = time::timeout_at(deadline, events.next()).await {
+        let id = match event {
+            CentralEvent::DeviceDiscovered(id)
+            | CentralEvent::DeviceUpdated(id)
+            | CentralEvent::ServicesAdvertisement { id, .. } => id,
+            _ => continue,
+        };
+
+        // Devices can disappear between being announced and being looked up, just skip them
+        let Ok(peripheral) = adapter.peripheral(&id).await else {
+            continue;
+        };
+        let Some(properties) = peripheral.properties().await? else {
+            continue;
+        };
+        if !is_root_advertisement(&properties)
+            || !filter.matches(
+                properties.local_name.as_deref(),
+                properties.address,
+                properties.rssi,
+            )
+        {
+            continue;
+        }
+
+        let candidate = RootCandidate {
+            name: properties.local_name,
+            address: properties.address,
+            rssi: properties.rssi,
+            peripheral,
+        };
+        // Robots advertise repeatedly, keep the latest signal strength rather than listing them twice
+        match candidates
+            .iter_mut()
+            .find(|existing| existing.address == candidate.address)
+        {
+            Some(existing) => *existing = candidate,
+            None => {
+                println!("Found {}", candidate);
+                candidates.push(candidate);
+                if first_only {
+                    break;
                 }
             }
         }
     }
 
-    None
+    for adapter in adapters.iter() {
+        adapter.stop_scan().await?;
+    }
+    Ok(candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn filters_by_name_address_and_rssi() {
+        let address = BDAddr::from_str("11:22:33:44:55:66").unwrap();
+        let other = BDAddr::from_str("66:55:44:33:22:11").unwrap();
+
+        let everything = DiscoveryFilter::default();
+        assert!(everything.matches(None, address, None));
+
+        let by_name = DiscoveryFilter {
+            name: Some("Root 2".to_string()),
+            ..Default::default()
+        };
+        assert!(by_name.matches(Some("Root 2"), address, None));
+        assert!(!by_name.matches(Some("Root"), address, None));
+        assert!(!by_name.matches(None, address, None));
+
+        let by_address = DiscoveryFilter {
+            address: Some(address),
+            ..Default::default()
+        };
+        assert!(by_address.matches(None, address, None));
+        assert!(!by_address.matches(None, other, None));
+
+        let nearby = DiscoveryFilter {
+            min_rssi: Some(-70),
+            ..Default::default()
+        };
+        assert!(nearby.matches(None, address, Some(-60)));
+        assert!(!nearby.matches(None, address, Some(-80)));
+        assert!(!nearby.matches(None, address, None));
+    }
 }
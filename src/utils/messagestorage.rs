@@ -1,82 +1,164 @@
 use std::collections::HashMap;
 use std::hash::Hash;
-use std::sync::{Arc, Condvar, Mutex};
+use std::sync::Mutex;
 use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::oneshot;
 
-// Bluetooth messages are polled asynchronously by a seperate thread. This storage class
-// takes the messages, holds them, and is accessible from both that thread and the main thread.
-// TODO: Really want to use a RWLock here instead of a Mutex but CondVar doesnt support it.
-//  If perf is an issue might need to re-evaluate
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MessageWaitError {
+    #[error("Timed out after {0:?} waiting for message")]
+    TimedOut(Duration),
+    #[error("Another waiter took over while waiting for message")]
+    Superseded,
+}
+
+// Bluetooth messages are polled asynchronously by a seperate task. This storage class
+// takes the messages, holds them, and hands them to whoever is waiting for them.
+// Each waiter gets its own oneshot channel so any number of messages can be awaited at once
+// without blocking a thread.
 pub struct MessageStorage<T: Eq + Hash, V> {
-    cond_var: Condvar,
-    messages: Arc<Mutex<HashMap<T, V>>>,
+    state: Mutex<MessageStorageState<T, V>>,
+}
+
+struct MessageStorageState<T, V> {
+    // Messages which arrived before anyone was waiting for them
+    messages: HashMap<T, V>,
+    waiters: HashMap<T, oneshot::Sender<V>>,
 }
 
-impl<T: Eq + Hash, V> MessageStorage<T, V> {
+impl<T: Eq + Hash + Clone, V> MessageStorage<T, V> {
     pub fn new() -> MessageStorage<T, V> {
         MessageStorage {
-            cond_var: Condvar::new(),
-            messages: Arc::new(Mutex::new(HashMap::new())),
+            state: Mutex::new(MessageStorageState {
+                messages: HashMap::new(),
+                waiters: HashMap::new(),
+            }),
         }
     }
 
-    // Wait for a message to be received
-    // TODO: Instead of blocking make this into a future?
-    pub fn wait_for_message(&self, key: T) -> Option<V> {
-        // Check to see if the message has been received
-        // Note that any message we receive will notify the cond_var so we might get a few false
-        // positives before actually getting the message we want.
-        loop {
-            let mut messages = self.messages.lock().unwrap();
-
-            // Try and remove the message
-            let message_opt = messages.remove(&key);
-            if message_opt.is_some() {
-                // Message has been received, done waiting
-                return Some(message_opt.unwrap());
-            } else {
-                // Otherwise wait until another message is received to try again
-                // TODO: Make timeout configurable?
-                let res = self
-                    .cond_var
-                    .wait_timeout(messages, Duration::from_secs(10));
-
-                if res.is_err() {
-                    return None;
+    // Wait for a message to be received, giving up after the timeout
+    pub async fn wait_for_message(&self, key: T, timeout: Duration) -> Result<V, MessageWaitError> {
+        let receiver = {
+            let mut state = self.state.lock().unwrap();
+
+            // The message might already be here
+            if let Some(message) = state.messages.remove(&key) {
+                return Ok(message);
+            }
+
+            let (sender, receiver) = oneshot::channel();
+            state.waiters.insert(key.clone(), sender);
+            receiver
+        };
+
+        match tokio::time::timeout(timeout, receiver).await {
+            Ok(Ok(message)) => Ok(message),
+            Ok(Err(_)) => Err(MessageWaitError::Superseded),
+            Err(_) => {
+                // Stop waiting so a late message gets stored rather than sent nowhere
+                let mut state = self.state.lock().unwrap();
+                if state
+                    .waiters
+                    .get(&key)
+                    .is_some_and(|sender| sender.is_closed())
+                {
+                    state.waiters.remove(&key);
                 }
+                Err(MessageWaitError::TimedOut(timeout))
             }
         }
     }
 
-    // Put a message in storage, and notify any waiters
+    // Throw away a stored message nobody waited for
+    pub fn remove_message(&self, key: &T) -> Option<V> {
+        self.state.lock().unwrap().messages.remove(key)
+    }
+
+    // Put a message in storage, or hand it straight to the waiter if there is one
     pub fn put_message(&self, key: T, message: V) {
-        self.messages.lock().unwrap().insert(key, message);
-        self.cond_var.notify_all();
+        let mut state = self.state.lock().unwrap();
+
+        let message = match state.waiters.remove(&key) {
+            Some(sender) => match sender.send(message) {
+                Ok(()) => return,
+                // Waiter gave up, hold on to the message instead
+                Err(message) => message,
+            },
+            None => message,
+        };
+
+        state.messages.insert(key, message);
+    }
+}
+
+impl<T: Eq + Hash + Clone, V> Default for MessageStorage<T, V> {
+    fn default() -> Self {
+        MessageStorage::new()
     }
 }
 
 #[cfg(test)]
 mod message_storage_tests {
-    use super::MessageStorage;
-    use std::thread;
+    use super::{MessageStorage, MessageWaitError};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn can_add_message_and_wait() {
+        let storage: Arc<MessageStorage<u8, u8>> = Arc::new(MessageStorage::new());
 
-    #[test]
-    fn can_add_message_and_wait() {
-        let mut message_count = 0;
+        let put_storage = storage.clone();
+        tokio::spawn(async move {
+            put_storage.put_message(0x01, 0x1A);
+        });
+
+        let message = storage.wait_for_message(0x01, Duration::from_secs(1)).await;
+        assert_eq!(message, Ok(0x1A));
+    }
+
+    #[tokio::test]
+    async fn can_receive_message_before_waiting() {
         let storage: MessageStorage<u8, u8> = MessageStorage::new();
 
-        thread::scope(|s| {
-            s.spawn(|| {
-                storage.put_message(0x01, 0x1A);
-            });
-            s.spawn(|| {
-                let message = storage.wait_for_message(0x01);
-                if message.unwrap() == 0x1A {
-                    message_count += 1;
-                }
-            });
+        storage.put_message(0x01, 0x1A);
+
+        let message = storage.wait_for_message(0x01, Duration::from_secs(1)).await;
+        assert_eq!(message, Ok(0x1A));
+    }
+
+    #[tokio::test]
+    async fn can_wait_for_messages_concurrently() {
+        let storage: Arc<MessageStorage<u8, u8>> = Arc::new(MessageStorage::new());
+
+        let first = storage.wait_for_message(0x01, Duration::from_secs(1));
+        let second = storage.wait_for_message(0x02, Duration::from_secs(1));
+
+        let put_storage = storage.clone();
+        tokio::spawn(async move {
+            put_storage.put_message(0x02, 0x2B);
+            put_storage.put_message(0x01, 0x1A);
         });
 
-        assert_eq!(1, message_count);
+        assert_eq!(tokio::join!(first, second), (Ok(0x1A), Ok(0x2B)));
+    }
+
+    #[tokio::test]
+    async fn times_out_and_keeps_late_message() {
+        let storage: MessageStorage<u8, u8> = MessageStorage::new();
+
+        let message = storage
+            .wait_for_message(0x01, Duration::from_millis(10))
+            .await;
+        assert_eq!(
+            message,
+            Err(MessageWaitError::TimedOut(Duration::from_millis(10)))
+        );
+
+        storage.put_message(0x01, 0x1A);
+        let message = storage
+            .wait_for_message(0x01, Duration::from_millis(10))
+            .await;
+        assert_eq!(message, Ok(0x1A));
     }
 }
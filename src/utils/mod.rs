@@ -1,8 +1,13 @@
 mod bluetoothutils;
+pub use self::bluetoothutils::discover_roots;
+pub use self::bluetoothutils::find_root;
 pub use self::bluetoothutils::find_root_peripheral;
+pub use self::bluetoothutils::DiscoveryFilter;
+pub use self::bluetoothutils::RootCandidate;
 
 mod messagestorage;
 pub use self::messagestorage::MessageStorage;
+pub use self::messagestorage::MessageWaitError;
 
 mod pointutils;
 pub use self::pointutils::calculate_angle;
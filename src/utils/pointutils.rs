@@ -1,3 +1,4 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Point {
     pub x_coord: f32,
     pub y_coord: f32,